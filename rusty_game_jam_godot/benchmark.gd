@@ -0,0 +1,23 @@
+extends Node
+
+# spawns a herd through main.tscn and prints the average time spent in _process per frame
+
+export var warmup_frames := 60
+export var measured_frames := 600
+
+var frame := 0
+var total_process_time := 0.0
+
+
+func _process(_delta: float) -> void:
+	frame += 1
+	if frame <= warmup_frames:
+		return
+	total_process_time += Performance.get_monitor(Performance.TIME_PROCESS)
+	if frame == warmup_frames + measured_frames:
+		var animals: int = get_tree().get_nodes_in_group("animals").size()
+		var per_frame := total_process_time / measured_frames * 1000.0
+		print("%d animals: %.3f ms per frame, %.4f ms per animal" % [
+			animals, per_frame, per_frame / max(animals, 1)
+		])
+		get_tree().quit()
@@ -1,17 +1,33 @@
-use crate::{animal_controller::AnimalController, animal_spawner::AnimalSpawner};
+use crate::behavior::BehaviorParams;
+use crate::collider::{capsule_height, extruded_hull, segment_capsules, ColliderShape, HALF_DEPTH};
+use crate::limb_mesh::{
+    build_animal_meshes, match_texture_indices, LimbMeshData, MeshStyle, MAX_TEXTURES,
+    MAX_TEXTURE_ROWS,
+};
+use crate::ragdoll::ragdoll_bones;
 use gdnative::{
     api::{rigid_body::Mode, *},
     prelude::*,
 };
 use itertools::Itertools;
+use rand::Rng;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::iter::{self, zip};
+use std::ptr;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Animal {
-    body: Limb,
+    pub body: Limb,
+    #[serde(default)]
+    pub behavior: BehaviorParams,
 }
 
 impl Animal {
+    /// only the templates the creature files are checked against build animals by hand
+    #[cfg(test)]
     pub fn new(body: Vec<BodyPoint>) -> Self {
         Self {
             body: Limb {
@@ -19,31 +35,359 @@ impl Animal {
                 texture_displacement: 0,
                 displacement: Vector3::ZERO,
                 name: "spine".to_string(),
+                role: None,
+                side: None,
             },
+            behavior: BehaviorParams::default(),
+        }
+    }
+
+    /// check for everything that would otherwise blow up while building the skeleton and meshes
+    pub fn validate(&self) -> Vec<AnimalError> {
+        let mut errors = Vec::new();
+        let mut names = HashSet::new();
+        let mut limbs = vec![&self.body];
+        while let Some(limb) = limbs.pop() {
+            if !names.insert(limb.name.clone()) {
+                errors.push(AnimalError::DuplicateLimbName(limb.name.clone()));
+            }
+            if limb.body.is_empty() {
+                errors.push(AnimalError::EmptyLimb(limb.name.clone()));
+            }
+            for (i, point) in limb.body.iter().enumerate() {
+                if !point.size.is_finite() || point.size < 0.0 {
+                    errors.push(AnimalError::InvalidSize {
+                        limb: limb.name.clone(),
+                        index: i,
+                        size: point.size,
+                    });
+                }
+                if !point.dir.x.is_finite() || !point.dir.y.is_finite() {
+                    errors.push(AnimalError::InvalidDirection {
+                        limb: limb.name.clone(),
+                        index: i,
+                    });
+                }
+                if point.texture_indices.is_empty() {
+                    errors.push(AnimalError::MissingTexture {
+                        limb: limb.name.clone(),
+                        index: i,
+                    });
+                }
+                if point.texture_indices.len() > MAX_TEXTURES {
+                    errors.push(AnimalError::TooManyTextures {
+                        limb: limb.name.clone(),
+                        index: i,
+                        count: point.texture_indices.len(),
+                    });
+                }
+                for (texture, _) in &point.texture_indices {
+                    if *texture >= MAX_TEXTURE_ROWS {
+                        errors.push(AnimalError::TextureOutOfRange {
+                            limb: limb.name.clone(),
+                            index: i,
+                            texture: *texture,
+                        });
+                    }
+                }
+                limbs.extend(&point.limbs);
+            }
+            // points with no textures or too many of them have already been reported
+            let textures_fit =
+                |point: &BodyPoint| (1..=MAX_TEXTURES).contains(&point.texture_indices.len());
+            for (i, (first, second)) in limb.body.iter().tuple_windows().enumerate() {
+                if textures_fit(first)
+                    && textures_fit(second)
+                    && match_texture_indices(&first.texture_indices, &second.texture_indices)
+                        .is_none()
+                {
+                    errors.push(AnimalError::IncompatibleTextures {
+                        limb: limb.name.clone(),
+                        index: i,
+                    });
+                }
+            }
         }
+        errors
     }
 }
 
+/// everything that can be wrong with an animal definition or a blend
 #[derive(Debug, Clone, PartialEq)]
+pub enum AnimalError {
+    NoAnimals,
+    EmptyGradient,
+    NoWeight(usize),
+    EmptyLimb(String),
+    DuplicateLimbName(String),
+    InvalidSize {
+        limb: String,
+        index: usize,
+        size: f32,
+    },
+    InvalidDirection {
+        limb: String,
+        index: usize,
+    },
+    MissingTexture {
+        limb: String,
+        index: usize,
+    },
+    IncompatibleTextures {
+        limb: String,
+        index: usize,
+    },
+    TooManyTextures {
+        limb: String,
+        index: usize,
+        count: usize,
+    },
+    TextureOutOfRange {
+        limb: String,
+        index: usize,
+        texture: usize,
+    },
+    MissingBone(String),
+}
+
+impl fmt::Display for AnimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimalError::NoAnimals => write!(f, "can't blend 0 animals together"),
+            AnimalError::EmptyGradient => write!(f, "blend gradient has no values"),
+            AnimalError::NoWeight(i) => {
+                write!(f, "no animal contributes any weight to body point {}", i)
+            }
+            AnimalError::EmptyLimb(limb) => write!(f, "limb '{}' has no body points", limb),
+            AnimalError::DuplicateLimbName(limb) => {
+                write!(f, "there is more than one limb called '{}'", limb)
+            }
+            AnimalError::InvalidSize { limb, index, size } => {
+                write!(f, "'{}' point {} has an invalid size {}", limb, index, size)
+            }
+            AnimalError::InvalidDirection { limb, index } => {
+                write!(f, "'{}' point {} has an invalid direction", limb, index)
+            }
+            AnimalError::MissingTexture { limb, index } => {
+                write!(f, "'{}' point {} has no texture", limb, index)
+            }
+            AnimalError::IncompatibleTextures { limb, index } => write!(
+                f,
+                "'{}' points {} and {} don't share a texture to transition between, or use more \
+                 than {} textures between them",
+                limb,
+                index,
+                index + 1,
+                MAX_TEXTURES
+            ),
+            AnimalError::TooManyTextures { limb, index, count } => write!(
+                f,
+                "'{}' point {} has {} textures, it can have at most {}",
+                limb, index, count, MAX_TEXTURES
+            ),
+            AnimalError::TextureOutOfRange {
+                limb,
+                index,
+                texture,
+            } => write!(
+                f,
+                "'{}' point {} uses texture {}, the atlas only has room for {}",
+                limb, index, texture, MAX_TEXTURE_ROWS
+            ),
+            AnimalError::MissingBone(bone) => write!(f, "bone {} does not exist", bone),
+        }
+    }
+}
+
+impl std::error::Error for AnimalError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Limb {
+    #[serde(default)]
     pub displacement: Vector3,
+    #[serde(default)]
     pub texture_displacement: usize,
     pub body: Vec<BodyPoint>,
     pub name: String,
+    /// guessed from the name when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<LimbRole>,
+    /// guessed from the name when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<LimbSide>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Limb {
+    pub fn role(&self) -> LimbRole {
+        self.role.unwrap_or_else(|| LimbRole::from_name(&self.name))
+    }
+
+    pub fn side(&self) -> LimbSide {
+        self.side.unwrap_or_else(|| LimbSide::from_name(&self.name))
+    }
+}
+
+/// what a limb is for, decides how the controller animates it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToVariant, FromVariant)]
+pub enum LimbRole {
+    Spine,
+    Leg,
+    Arm,
+    Tail,
+    Neck,
+    /// ears, combs and anything else that just flops about
+    Appendage,
+}
+
+impl LimbRole {
+    /// anything that isn't obviously something else is walked on
+    pub fn from_name(name: &str) -> Self {
+        if name.starts_with("spine") {
+            Self::Spine
+        } else if name.contains("arm") {
+            Self::Arm
+        } else if name.contains("tail") {
+            Self::Tail
+        } else if name.contains("neck") {
+            Self::Neck
+        } else if name.contains("ear") || name.contains("comb") {
+            Self::Appendage
+        } else {
+            Self::Leg
+        }
+    }
+}
+
+/// which side of the body a limb hangs off, front is the side facing the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToVariant, FromVariant)]
+pub enum LimbSide {
+    Front,
+    Center,
+    Back,
+}
+
+impl LimbSide {
+    pub fn from_name(name: &str) -> Self {
+        if name.starts_with("front") {
+            Self::Front
+        } else if name.starts_with("back") {
+            Self::Back
+        } else {
+            Self::Center
+        }
+    }
+}
+
+/// what the controller needs to know about one limb of a spawned animal
+#[derive(Debug, Clone, PartialEq, ToVariant, FromVariant)]
+pub struct LimbInfo {
+    pub name: String,
+    pub role: LimbRole,
+    pub side: LimbSide,
+    /// skeleton bone ids from the root of the limb to its tip
+    pub bones: Vec<i64>,
+}
+
+/// which species a spawned animal was blended from and how much of each went into every point of
+/// its spine
+#[derive(Debug, Clone, Default, PartialEq, ToVariant, FromVariant)]
+pub struct SpeciesInfo {
+    pub species: Vec<String>,
+    /// one gradient per species
+    pub gradients: Vec<Vec<f32>>,
+}
+
+/// the name of the node between an animal's body and its skeleton
+pub const CONTAINER_NAME: &str = "Container";
+
+/// everything an animal's controller needs to know about it, handed over when it's spawned
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimalInfo {
+    /// half the size of the box around the animal's meshes
+    pub dimensions: Vector3,
+    /// the middle of the box around the animal's meshes, relative to the body
+    pub center: Vector3,
+    pub limbs: Vec<LimbInfo>,
+    pub behavior: BehaviorParams,
+    /// left empty by `create_animal`, whoever blended the animal knows what went into it
+    pub species: SpeciesInfo,
+    /// seeds the animal's own randomness
+    pub seed: u64,
+}
+
+/// a freshly created animal, not in the scene tree yet
+pub struct SpawnedAnimal {
+    pub body: Ref<RigidBody, Unique>,
+    pub info: AnimalInfo,
+}
+
+/// describe every limb of an animal, `bones` maps bone names to skeleton bone ids
+pub fn limb_infos(
+    animal: &Animal,
+    bones: &HashMap<String, i64>,
+) -> Result<Vec<LimbInfo>, AnimalError> {
+    let mut infos = Vec::new();
+    let mut limbs = vec![&animal.body];
+    while let Some(limb) = limbs.pop() {
+        let ids = (0..limb.body.len())
+            .map(|i| {
+                let name = format!("{}_{}", limb.name, i);
+                bones
+                    .get(&name)
+                    .copied()
+                    .ok_or(AnimalError::MissingBone(name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        infos.push(LimbInfo {
+            name: limb.name.clone(),
+            role: limb.role(),
+            side: limb.side(),
+            bones: ids,
+        });
+        for point in &limb.body {
+            limbs.extend(&point.limbs);
+        }
+    }
+    Ok(infos)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BodyPoint {
     pub dir: Vector2,
     pub size: f32,
-    pub texture_indices: [Option<(usize, f32)>; 2],
+    /// which rows of the texture atlas show on this point and how much of each, heaviest first
+    #[serde(deserialize_with = "deserialize_texture_indices")]
+    pub texture_indices: Vec<(usize, f32)>,
+    #[serde(default)]
     pub discontinuous: bool,
+    #[serde(default)]
     pub limbs: Vec<Limb>,
 }
 
+/// creature files written before points could have more than two textures store them as a pair
+/// of optional textures, which still load
+fn deserialize_texture_indices<'de, D>(deserializer: D) -> Result<Vec<(usize, f32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TextureIndices {
+        List(Vec<(usize, f32)>),
+        Pair([Option<(usize, f32)>; 2]),
+    }
+    Ok(match TextureIndices::deserialize(deserializer)? {
+        TextureIndices::List(textures) => textures,
+        TextureIndices::Pair(textures) => textures.into_iter().flatten().collect(),
+    })
+}
+
 pub struct BodyGradient(pub Vec<f32>);
 impl BodyGradient {
     pub fn decreasing_linear(n: usize) -> Self {
+        if n == 1 {
+            return Self(vec![1.0]);
+        }
         let mut v = Vec::new();
         for i in 0..n {
             v.push(((n.saturating_sub(i + 1)) as f32) / ((n - 1) as f32));
@@ -52,6 +396,9 @@ impl BodyGradient {
     }
 
     pub fn increasing_linear(n: usize) -> Self {
+        if n == 1 {
+            return Self(vec![1.0]);
+        }
         let mut v = Vec::new();
         for i in 0..n {
             v.push((i.saturating_sub(1) as f32) / ((n - 1) as f32));
@@ -74,325 +421,437 @@ impl BodyGradient {
     }
 }
 
-pub fn blend_animals(animals: &[(Animal, BodyGradient)]) -> Animal {
+/// ready made sets of gradients for blending a number of animals together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientPreset {
+    /// every animal gets the same weight everywhere
+    Even,
+    /// each animal peaks at its own spot along the body, the first at the head and the last at the tail
+    Linear,
+    /// weights wander randomly along the body
+    Random,
+}
+
+impl GradientPreset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "even" => Some(Self::Even),
+            "linear" => Some(Self::Linear),
+            "random" => Some(Self::Random),
+            _ => None,
+        }
+    }
+
+    /// one gradient per animal, `lens` are the body lengths of the animals being blended
+    pub fn gradients(self, lens: &[usize], rng: &mut impl Rng) -> Vec<BodyGradient> {
+        let count = lens.len();
+        let len = lens.iter().copied().max().unwrap_or(0);
+        let mut gradients: Vec<BodyGradient> = match self {
+            Self::Linear if count > 1 => {
+                let spacing = len.saturating_sub(1).max(1) as f32 / (count - 1) as f32;
+                (0..count)
+                    .map(|k| {
+                        BodyGradient(
+                            (0..len)
+                                .map(|i| {
+                                    (1.0 - (i as f32 - k as f32 * spacing).abs() / spacing).max(0.0)
+                                })
+                                .collect(),
+                        )
+                    })
+                    .collect()
+            }
+            Self::Even | Self::Linear => (0..count).map(|_| BodyGradient(vec![1.0; len])).collect(),
+            Self::Random => (0..count)
+                .map(|_| {
+                    let mut weight = rng.gen::<f32>();
+                    BodyGradient(
+                        (0..len)
+                            .map(|_| {
+                                weight = (weight + rng.gen_range(-0.3..0.3)).clamp(0.0, 1.0);
+                                weight
+                            })
+                            .collect(),
+                    )
+                })
+                .collect(),
+        };
+
+        // past the end of the shorter animals, make sure somebody is still contributing
+        for i in 0..len {
+            let has_point = |k: &usize| i < lens[*k];
+            if (0..count)
+                .filter(has_point)
+                .all(|k| gradients[k].0[i] <= 0.0)
+            {
+                for k in (0..count).filter(has_point) {
+                    gradients[k].0[i] = 1.0;
+                }
+            }
+        }
+        gradients
+    }
+}
+
+pub fn blend_animals(animals: &[(Animal, BodyGradient)]) -> Result<Animal, AnimalError> {
+    if animals.is_empty() {
+        return Err(AnimalError::NoAnimals);
+    }
+    if animals.iter().any(|(_, gradient)| gradient.0.is_empty()) {
+        return Err(AnimalError::EmptyGradient);
+    }
+    let spines = animals
+        .iter()
+        .map(|(animal, gradient)| (&animal.body, gradient.0.clone()))
+        .collect_vec();
+    let behaviors = animals
+        .iter()
+        .map(|(animal, gradient)| {
+            (
+                &animal.behavior,
+                gradient.0.iter().sum::<f32>() / gradient.0.len() as f32,
+            )
+        })
+        .collect_vec();
+    Ok(Animal {
+        body: blend_limbs(&spines)?,
+        behavior: BehaviorParams::blend(&behaviors),
+    })
+}
+
+/// the kind of limb, ignoring which pair it belongs to (`front_leg_2` -> `front_leg`)
+pub(crate) fn limb_role(name: &str) -> &str {
+    match name.rsplit_once('_') {
+        Some((role, suffix)) if suffix.chars().all(|c| c.is_ascii_digit()) => role,
+        _ => name,
+    }
+}
+
+/// find the limb on `other` that best corresponds to `limb`, which is attached `position` of the
+/// way along its parent, skipping any that are already `taken`. same name wins over same role,
+/// ties go to the closest attachment point
+fn match_limb<'a>(
+    limb: &Limb,
+    position: f32,
+    other: &'a Limb,
+    taken: &[&Limb],
+) -> Option<&'a Limb> {
+    let float_compare = |a: &f32, b: &f32| a.partial_cmp(b).unwrap_or(Ordering::Less);
+    other
+        .body
+        .iter()
+        .enumerate()
+        .flat_map(|(i, point)| {
+            let other_position = i as f32 / other.body.len() as f32;
+            point.limbs.iter().map(move |x| (x, other_position))
+        })
+        .filter(|(x, _)| limb_role(&x.name) == limb_role(&limb.name))
+        .filter(|(x, _)| !taken.iter().any(|y| ptr::eq(*x, *y)))
+        .min_by(|(a, a_position), (b, b_position)| {
+            (a.name != limb.name)
+                .cmp(&(b.name != limb.name))
+                .then_with(|| {
+                    float_compare(
+                        &(a_position - position).abs(),
+                        &(b_position - position).abs(),
+                    )
+                })
+        })
+        .map(|(x, _)| x)
+}
+
+/// the textures a blended body point keeps out of `textures`, heaviest first, so that it shares
+/// one with the point before it and the two of them don't use more than fit on a segment
+fn pick_textures(textures: Vec<(usize, f32)>, previous: &[(usize, f32)]) -> Vec<(usize, f32)> {
+    let shared = |texture: &(usize, f32)| previous.iter().any(|x| x.0 == texture.0);
+    let mut candidates = textures;
+    // the heaviest texture the previous point also has goes first, so the segment has something
+    // to fade through
+    if let Some(i) = candidates.iter().position(shared) {
+        let texture = candidates.remove(i);
+        candidates.insert(0, texture);
+    }
+
+    let mut picked: Vec<(usize, f32)> = Vec::new();
+    for texture in candidates {
+        let used = previous
+            .iter()
+            .chain(&picked)
+            .chain([&texture])
+            .map(|x| x.0)
+            .unique()
+            .count();
+        if picked.len() < MAX_TEXTURES && used <= MAX_TEXTURES {
+            picked.push(texture);
+        }
+    }
+    picked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Less));
+    picked
+}
+
+/// limbs the dominant parent doesn't have only show up on a blend if the parents that do have at
+/// least this much of the weight where they're attached
+const MIN_LIMB_SHARE: f32 = 0.25;
+
+/// how much of a limb `len` points long comes from one parent, starting at the parent's weight
+/// where the limb is attached and fading to the parent's average weight at the tip
+fn limb_gradient(gradient: &[f32], attached: usize, len: usize) -> Vec<f32> {
+    let root = gradient.get(attached).copied().unwrap_or(0.0);
+    let average = gradient.iter().sum::<f32>() / gradient.len().max(1) as f32;
+    (0..len)
+        .map(|i| root + (average - root) * i as f32 / len.saturating_sub(1).max(1) as f32)
+        .collect()
+}
+
+/// a copy of a limb shrunk away to nothing, which stands in for it on parents that don't have it
+/// so it grows in with the weight of the parents that do
+fn vestigial(limb: &Limb) -> Limb {
+    let mut limb = limb.clone();
+    for point in &mut limb.body {
+        point.dir = Vector2::ZERO;
+        point.size = 0.0;
+        point.limbs.clear();
+    }
+    limb
+}
+
+/// blend limbs point by point using their gradients. limbs attached along the way are matched up
+/// between the parents and blended with their own gradients, which carry on from the parents'
+/// weights where they're attached. parents without a matching limb blend in a vestigial one, and
+/// limbs the dominant parent doesn't have are left off if too little of the weight has them
+fn blend_limbs(limbs: &[(&Limb, Vec<f32>)]) -> Result<Limb, AnimalError> {
     let float_compare = |a: &f32, b: &f32| a.partial_cmp(b).unwrap_or(Ordering::Less);
-    let body_len = animals
+    let body_len = limbs
         .iter()
-        .map(|(_, gradient)| gradient.0.len())
+        .map(|(_, gradient)| gradient.len())
         .max()
-        .expect("can't blend 0 animals together");
+        .ok_or(AnimalError::NoAnimals)?;
 
     let mut body: Vec<BodyPoint> = Vec::new();
+    // limbs that have already been blended into a child limb
+    let mut taken: Vec<&Limb> = Vec::new();
     for i in 0..body_len {
         let mut dir = Vector2::ZERO;
         let mut size = 0.0;
-        let mut texture_indices = Vec::new();
+        let mut texture_indices: Vec<(usize, f32)> = Vec::new();
         let mut grad_sum = 0.0;
         let mut discontinuous = true;
-        let mut limbs = Vec::new();
+        let mut dominant = None;
         let mut max_grad = f32::MIN;
-        for (animal, body_grad) in animals {
-            if let Some(body_point) = animal.body.body.get(i) {
-                let grad = *body_grad.0.get(i).unwrap_or(&0f32);
+        for (j, (limb, body_grad)) in limbs.iter().enumerate() {
+            if let Some(body_point) = limb.body.get(i) {
+                let grad = *body_grad.get(i).unwrap_or(&0f32);
                 dir += body_point.dir * grad;
                 size += body_point.size * grad;
-                body_point.texture_indices.iter().for_each(|x| {
-                    if let Some(x) = x {
-                        texture_indices.push((x.0, x.1 * grad));
+                for (texture, weight) in &body_point.texture_indices {
+                    match texture_indices.iter_mut().find(|x| x.0 == *texture) {
+                        Some(existing) => existing.1 += weight * grad,
+                        None => texture_indices.push((*texture, weight * grad)),
                     }
-                });
+                }
                 if !body_point.discontinuous {
                     discontinuous = false;
                 }
                 grad_sum += grad;
 
                 if grad > max_grad {
-                    limbs = body_point.limbs.clone();
+                    dominant = Some(j);
                     max_grad = grad;
                 }
             }
         }
+        if grad_sum <= 0.0 || texture_indices.is_empty() {
+            return Err(AnimalError::NoWeight(i));
+        }
         size /= grad_sum;
         dir /= grad_sum;
 
-        let tex_compare = |a: &(usize, f32), b: &(usize, f32)| float_compare(&a.1, &b.1);
-        texture_indices.sort_by(tex_compare);
-
-        let first_tex_index = texture_indices[texture_indices.len() - 1];
-        let second_tex_index = if let Some(body_point) = body.get(body.len().saturating_sub(1)) {
-            let prev_contains = |tex_index: &(usize, f32)| {
-                body_point
-                    .texture_indices
-                    .iter()
-                    .any(|x| x.iter().any(|x| x.0 == tex_index.0))
-            };
-            if !prev_contains(&first_tex_index) {
-                texture_indices
-                    .into_iter()
-                    .filter(|x| {
-                        body_point
-                            .texture_indices
-                            .iter()
-                            .any(|y| y.iter().any(|y| x.0 == y.0))
-                    })
-                    .max_by(|a, b| float_compare(&a.1, &b.1))
-            } else if texture_indices.len() == 1
-                || !prev_contains(&texture_indices[texture_indices.len() - 2])
-            {
-                None
-            } else {
-                Some(texture_indices[texture_indices.len() - 2])
+        let mut child_limbs = Vec::new();
+        if let Some(dominant) = dominant {
+            let grads = limbs
+                .iter()
+                .map(|(limb, body_grad)| match limb.body.get(i) {
+                    Some(_) => body_grad.get(i).copied().unwrap_or(0.0),
+                    None => 0.0,
+                })
+                .collect_vec();
+            // the dominant parent's limbs go first, so they get first pick of the matches
+            let parents = iter::once(dominant)
+                .chain((0..limbs.len()).filter(|j| *j != dominant && grads[*j] > 0.0))
+                .collect_vec();
+            for j in parents {
+                let (parent, _) = limbs[j];
+                let position = i as f32 / parent.body.len() as f32;
+                for child in &parent.body[i].limbs {
+                    if taken.iter().any(|x| ptr::eq(*x, child)) {
+                        continue;
+                    }
+                    let mut carriers = vec![(j, child)];
+                    for (k, (other, _)) in limbs.iter().enumerate() {
+                        if k == j || grads[k] <= 0.0 {
+                            continue;
+                        }
+                        if let Some(other) = match_limb(child, position, other, &taken) {
+                            carriers.push((k, other));
+                        }
+                    }
+                    let share = carriers.iter().map(|(k, _)| grads[*k]).sum::<f32>() / grad_sum;
+                    if j != dominant && share < MIN_LIMB_SHARE {
+                        continue;
+                    }
+                    taken.extend(carriers.iter().map(|(_, x)| *x));
+
+                    let shrunk = vestigial(child);
+                    let matched = (0..limbs.len())
+                        .filter(|k| grads[*k] > 0.0)
+                        .map(|k| {
+                            let limb = carriers
+                                .iter()
+                                .find(|(carrier, _)| *carrier == k)
+                                .map_or(&shrunk, |(_, x)| *x);
+                            (limb, limb_gradient(&limbs[k].1, i, limb.body.len()))
+                        })
+                        .collect_vec();
+                    child_limbs.push(blend_limbs(&matched)?);
+                }
             }
-        } else {
-            Some(texture_indices[texture_indices.len() - 2])
-        };
+        }
 
-        let texture_indices = [Some(first_tex_index), second_tex_index];
+        texture_indices.sort_by(|a, b| float_compare(&b.1, &a.1));
+        let texture_indices = match body.last() {
+            Some(previous) => pick_textures(texture_indices, &previous.texture_indices),
+            None => texture_indices.into_iter().take(MAX_TEXTURES).collect(),
+        };
 
         body.push(BodyPoint {
             dir,
             size,
             texture_indices,
             discontinuous,
-            limbs,
+            limbs: child_limbs,
         });
     }
 
     // normalize body weights
     for body_point in &mut body {
-        let total = body_point
-            .texture_indices
-            .iter()
-            .map(|x| x.iter().map(|x| x.1).sum::<f32>())
-            .sum::<f32>();
-
+        let total = body_point.texture_indices.iter().map(|x| x.1).sum::<f32>();
         for texture_index in &mut body_point.texture_indices {
-            if let Some(texture_index) = texture_index {
-                texture_index.1 /= total;
-            }
+            texture_index.1 /= total;
         }
     }
-    Animal::new(body)
-}
 
-fn get_uv(texture_block_size: (f32, f32), x: usize, y: usize, uv: (f32, f32)) -> Vector2 {
-    Vector2::new(
-        (x as f32 + uv.0) * texture_block_size.0,
-        (y as f32 + uv.1) * texture_block_size.1,
-    )
-}
+    let weights = limbs
+        .iter()
+        .map(|(_, gradient)| gradient.iter().sum::<f32>() / gradient.len().max(1) as f32)
+        .collect_vec();
+    let weight_sum = weights.iter().sum::<f32>();
+    let displacement = if weight_sum > 0.0 {
+        zip(limbs, &weights).fold(Vector3::ZERO, |acc, ((limb, _), weight)| {
+            acc + limb.displacement * *weight
+        }) / weight_sum
+    } else {
+        limbs[0].0.displacement
+    };
+    let (main_limb, _) = zip(limbs, &weights)
+        .max_by(|(_, a), (_, b)| float_compare(a, b))
+        .map(|(x, _)| x)
+        .ok_or(AnimalError::NoAnimals)?;
 
-fn get_bone_id(skeleton: &Ref<Skeleton, Unique>, name: &str) -> i64 {
-    for i in 0..skeleton.get_bone_count() {
-        if skeleton.get_bone_name(i).to_string() == name {
-            return i;
-        }
-    }
-    unreachable!("bone {} does not exist", name)
+    Ok(Limb {
+        displacement,
+        texture_displacement: main_limb.texture_displacement,
+        body,
+        name: main_limb.name.clone(),
+        role: main_limb.role,
+        side: main_limb.side,
+    })
 }
 
-fn create_animal_skeleton(animal: &Animal) -> Ref<Skeleton, Unique> {
+/// skeleton bone ids by bone name
+type BoneIds = HashMap<String, i64>;
+
+/// build the skeleton, along with an index from bone names to bone ids so nothing has to scan
+/// the skeleton by name afterwards
+fn create_animal_skeleton(
+    animal: &Animal,
+) -> Result<(Ref<Skeleton, Unique>, BoneIds), AnimalError> {
     let skeleton = Skeleton::new();
+    let mut bones = HashMap::new();
     let mut limbs = vec![(&animal.body, None)];
     while let Some((limb, parent)) = limbs.pop() {
-        create_skeleton_limb(limb, &skeleton, parent);
+        create_skeleton_limb(limb, &skeleton, &mut bones, parent)?;
         for (i, point) in limb.body.iter().enumerate() {
             for new_limb in &point.limbs {
                 limbs.push((new_limb, Some(format!("{}_{}", limb.name, i))));
             }
         }
     }
-    skeleton
+    Ok((skeleton, bones))
 }
 
 pub fn create_skeleton_limb(
     limb: &Limb,
     skeleton: &Ref<Skeleton, Unique>,
+    bones: &mut BoneIds,
     parent_bone_name: Option<String>,
-) {
+) -> Result<(), AnimalError> {
+    let bone_id = |bones: &BoneIds, name: &str| {
+        bones
+            .get(name)
+            .copied()
+            .ok_or_else(|| AnimalError::MissingBone(name.to_string()))
+    };
     let mut last_dir = Vector2::new(limb.displacement.x, limb.displacement.y);
     for (i, point) in limb.body.iter().enumerate() {
         let bone_name = format!("{}_{}", limb.name, i);
+        let id = skeleton.get_bone_count();
         skeleton.add_bone(bone_name.clone());
+        bones.insert(bone_name, id);
         let bone_transform =
             Transform::IDENTITY.translated(Vector3::new(last_dir.x, last_dir.y, 0.0));
-        skeleton.set_bone_rest(get_bone_id(&skeleton, &bone_name), bone_transform);
+        skeleton.set_bone_rest(id, bone_transform);
         if i != 0 {
-            skeleton.set_bone_parent(
-                get_bone_id(&skeleton, &bone_name),
-                get_bone_id(&skeleton, &format!("{}_{}", limb.name, i - 1)),
-            );
+            skeleton.set_bone_parent(id, bone_id(bones, &format!("{}_{}", limb.name, i - 1))?);
         } else if let Some(parent_bone_name) = &parent_bone_name {
-            skeleton.set_bone_parent(
-                get_bone_id(skeleton, &bone_name),
-                get_bone_id(skeleton, parent_bone_name),
-            );
+            skeleton.set_bone_parent(id, bone_id(bones, parent_bone_name)?);
         }
         last_dir = point.dir;
     }
+    Ok(())
 }
 
 const COMPRESS_FLAGS_DEFAULT: i64 = 97280;
+/// the second uv holds whole atlas row numbers, which half floats can't keep exactly
+const COMPRESS_FLAGS: i64 = COMPRESS_FLAGS_DEFAULT & !Mesh::ARRAY_COMPRESS_TEX_UV2;
+/// the lightest a bone of a ragdoll can be, so tiny bones don't jitter
+const MIN_BONE_MASS: f32 = 0.1;
 pub fn create_limb_mesh(
-    limb: &Limb,
-    current_posn: Vector3,
-    skeleton: &Ref<Skeleton, Unique>,
+    mesh_data: &LimbMeshData,
     animal_material: &Ref<ShaderMaterial>,
-    texture_block_size: (f32, f32),
 ) -> Ref<MeshInstance, Unique> {
     let mesh = ArrayMesh::new();
     let st = SurfaceTool::new();
     st.begin(Mesh::PRIMITIVE_TRIANGLES);
 
-    let mut current_posn = current_posn;
-
-    let mut corner_vert = Vector3::new(f32::MAX, f32::MAX, 0.0);
-
-    let mut last_dir = limb.body[0].dir;
-    let average_dir = |a: Vector2, b: Vector2| (a + b) / 2.0;
-    for ((i, first), (_, second)) in limb.body.iter().enumerate().tuple_windows() {
-        let diff_first = if first.discontinuous {
-            last_dir.tangent().normalized() * first.size * 0.5
-        } else {
-            average_dir(first.dir, last_dir).tangent().normalized() * first.size * 0.5
-        };
-
-        let diff_second = if second.discontinuous {
-            diff_first
-        } else {
-            average_dir(second.dir, first.dir).tangent().normalized() * second.size * 0.5
-        };
-        last_dir = first.dir;
-
-        let [diff_first, diff_second, first_dir] =
-            [diff_first, diff_second, first.dir].map(|x| Vector3::new(x.x, x.y, 0.0));
-
-        let corners = [
-            (current_posn - diff_first, (0.0, 0.0)),
-            (current_posn + diff_first, (0.0, 1.0)),
-            (current_posn + diff_second + first_dir, (1.0, 1.0)),
-            (current_posn - diff_second + first_dir, (1.0, 0.0)),
-        ];
-        let center = (current_posn + first_dir * 0.5, (0.5, 0.5));
-        let quad = [
-            [corners[0], corners[1], center],
-            [corners[1], corners[2], center],
-            [corners[2], corners[3], center],
-            [corners[3], corners[0], center],
-        ];
-
-        for (index, tri) in quad.into_iter().enumerate() {
-            let (first_texture_indices, second_texture_indices) =
-                match (first.texture_indices, second.texture_indices) {
-                    ([Some(a), None], [Some(b), None]) => {
-                        if a.0 != b.0 {
-                            unreachable!()
-                        }
-                        (vec![a], vec![b])
-                    }
-                    ([Some(a), None], [Some(b), Some(c)]) => {
-                        if a.0 == b.0 {
-                            (vec![a, (c.0, 0.0)], vec![b, c])
-                        } else if a.0 == c.0 {
-                            (vec![(b.0, 0.0), a], vec![b, c])
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                    ([Some(a), Some(b)], [Some(c), None]) => {
-                        if c.0 == a.0 {
-                            (vec![a, b], vec![c, (b.0, 0.0)])
-                        } else if c.0 == b.0 {
-                            (vec![a, b], vec![(a.0, 0.0), c])
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                    ([Some(a), Some(b)], [Some(c), Some(d)]) => {
-                        if a.0 == c.0 && b.0 == d.0 {
-                            (vec![a, b], vec![c, d])
-                        } else if a.0 == d.0 && b.0 == c.0 {
-                            (vec![b, a], vec![c, d])
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                    _ => unreachable!(),
-                };
-
-            for (vert, uv) in tri {
-                let uvs = first_texture_indices
-                    .iter()
-                    .map(|tex| get_uv(texture_block_size, i + limb.texture_displacement, tex.0, uv))
-                    .collect_vec();
-
-                if uvs.len() == 1 {
-                    st.add_uv(uvs[0]);
-                    st.add_color(Color::from_rgba(0.0, 0.0, 1.0, index as f32));
-                } else if uvs.len() == 2 {
-                    st.add_uv(uvs[0]);
-                    let alphas = if uv.0 == 0.0 {
-                        &first_texture_indices
-                    } else {
-                        &second_texture_indices
-                    }
-                    .iter()
-                    .map(|x| x.1)
-                    .collect_vec();
-                    st.add_color(Color::from_rgba(
-                        uvs[1].x,
-                        uvs[1].y,
-                        alphas[0],
-                        index as f32,
-                    ))
-                }
-
-                let mut bones = PoolArray::new();
-                let mut weights = PoolArray::new();
-
-                let [first_bone, second_bone] = [i.saturating_sub(1), i]
-                    .map(|x| get_bone_id(&skeleton, &format!("{}_{}", limb.name, x)) as i32);
-
-                if uv.0 < 0.25 {
-                    bones.push(first_bone);
-                    weights.push(1.0);
-                } else if uv.0 > 0.75 {
-                    bones.push(second_bone);
-                    weights.push(1.0);
-                } else {
-                    bones.push(first_bone);
-                    bones.push(second_bone);
-                    weights.push(0.5);
-                    weights.push(0.5);
-                }
-
-                while bones.len() < 4 {
-                    bones.push(0);
-                    weights.push(0.0);
-                }
-                st.add_bones(bones);
-                st.add_weights(weights);
-
-                if vert.x < corner_vert.x {
-                    corner_vert.x = vert.x;
-                }
-
-                if vert.y < corner_vert.y {
-                    corner_vert.y = vert.y;
-                }
-
-                st.add_vertex(vert);
-            }
-        }
-
-        current_posn += first_dir;
+    for i in 0..mesh_data.vertices.len() {
+        st.add_uv(mesh_data.uvs[i]);
+        st.add_uv2(mesh_data.uv2s[i]);
+        st.add_color(mesh_data.colors[i]);
+        st.add_bones(PoolArray::from_slice(&mesh_data.bones[i]));
+        st.add_weights(PoolArray::from_slice(&mesh_data.weights[i]));
+        st.add_normal(mesh_data.normals[i]);
+        st.add_tangent(mesh_data.tangents[i]);
+        st.add_vertex(mesh_data.vertices[i]);
+    }
+    for index in &mesh_data.indices {
+        st.add_index(*index as i64);
     }
 
     mesh.add_surface_from_arrays(
         Mesh::PRIMITIVE_TRIANGLES,
         st.commit_to_arrays(),
         VariantArray::new_shared(),
-        COMPRESS_FLAGS_DEFAULT,
+        COMPRESS_FLAGS,
     );
 
     let limb_mesh = MeshInstance::new();
@@ -402,36 +861,126 @@ pub fn create_limb_mesh(
 }
 
 pub fn create_animal_meshes(
-    animal: &Animal,
-    skeleton: &Ref<Skeleton, Unique>,
+    meshes: &[(&Limb, LimbMeshData)],
     animal_material: &Ref<ShaderMaterial>,
-    texture_block_size: (f32, f32),
 ) -> Vec<Ref<MeshInstance, Unique>> {
-    let mut meshes = Vec::new();
-    let mut limbs = vec![(&animal.body, Vector3::ZERO)];
-    while let Some((limb, current_posn)) = limbs.pop() {
-        let mesh = create_limb_mesh(
-            limb,
-            current_posn + Vector3::new(limb.displacement.x, limb.displacement.y, 0.0),
-            skeleton,
-            animal_material,
-            texture_block_size,
-        );
-        mesh.set_transform(Transform::IDENTITY.translated(Vector3::new(
-            0.0,
-            0.0,
-            limb.displacement.z,
-        )));
-        meshes.push(mesh);
-        let mut current_posn = current_posn;
-        for point in &limb.body {
-            for new_limb in &point.limbs {
-                limbs.push((new_limb, current_posn));
-            }
-            current_posn += Vector3::new(point.dir.x, point.dir.y, 0.0);
+    meshes
+        .iter()
+        .map(|(limb, mesh_data)| {
+            let mesh = create_limb_mesh(mesh_data, animal_material);
+            mesh.set_transform(Transform::IDENTITY.translated(Vector3::new(
+                0.0,
+                0.0,
+                limb.displacement.z,
+            )));
+            mesh
+        })
+        .collect()
+}
+
+/// the collision shapes for an animal, `size` and `center` are half the size and the middle of the
+/// box around it
+fn create_collision_shapes(
+    collider: ColliderShape,
+    animal: &Animal,
+    meshes: &[(&Limb, LimbMeshData)],
+    size: Vector3,
+    center: Vector3,
+) -> Vec<Ref<CollisionShape, Unique>> {
+    match collider {
+        ColliderShape::Box => {
+            let shape = BoxShape::new();
+            shape.set_extents(size);
+            let collision_shape = CollisionShape::new();
+            collision_shape.translate(center);
+            collision_shape.set_shape(shape);
+            vec![collision_shape]
+        }
+        ColliderShape::Capsules | ColliderShape::CapsulesAndLegs => {
+            segment_capsules(animal, collider == ColliderShape::CapsulesAndLegs)
+                .into_iter()
+                .map(|capsule| {
+                    let shape = CapsuleShape::new();
+                    shape.set_radius(capsule.radius as f64);
+                    shape.set_height(capsule.height() as f64);
+                    // capsules lie along their z axis
+                    let transform =
+                        Transform::IDENTITY.translated((capsule.from + capsule.to) / 2.0);
+                    let collision_shape = CollisionShape::new();
+                    collision_shape.set_transform(
+                        if capsule.from.distance_to(capsule.to) > f32::EPSILON {
+                            transform.looking_at(capsule.to, Vector3::new(0.0, 0.0, 1.0))
+                        } else {
+                            transform
+                        },
+                    );
+                    collision_shape.set_shape(shape);
+                    collision_shape
+                })
+                .collect()
+        }
+        ColliderShape::ConvexHull => {
+            let vertices = meshes
+                .iter()
+                .flat_map(|(_, mesh_data)| mesh_data.vertices.iter().map(|x| vec2(*x)))
+                .collect::<Vec<_>>();
+            let shape = ConvexPolygonShape::new();
+            shape.set_points(PoolArray::from_vec(extruded_hull(&vertices)));
+            let collision_shape = CollisionShape::new();
+            collision_shape.set_shape(shape);
+            vec![collision_shape]
         }
     }
-    meshes
+}
+
+/// a physics body for each bone of an animal, which follow the skeleton around until the animal
+/// goes limp
+fn create_physical_bones(animal: &Animal) -> Vec<Ref<PhysicalBone, Unique>> {
+    ragdoll_bones(animal)
+        .into_iter()
+        .map(|bone| {
+            let segment = Vector3::new(bone.segment.x, bone.segment.y, 0.0);
+            let middle = segment / 2.0 + Vector3::new(0.0, 0.0, bone.depth);
+            let physical_bone = PhysicalBone::new();
+            physical_bone.set_name(format!("Physical_{}", bone.bone_name));
+            physical_bone.set("bone_name", bone.bone_name);
+            // the body sits in the middle of the bone, and bends around the start of it
+            physical_bone.set_body_offset(Transform::IDENTITY.translated(middle));
+            physical_bone.set_joint_offset(Transform::IDENTITY.translated(-middle));
+            physical_bone
+                .set_mass((segment.length() * bone.radius * 2.0).max(MIN_BONE_MASS) as f64);
+            match bone.limit {
+                // hinges turn around their z axis, the same way everything else in the animal
+                // turns
+                Some(limit) => {
+                    physical_bone.set_joint_type(PhysicalBone::JOINT_TYPE_HINGE);
+                    physical_bone.set("joint_constraints/angular_limit_enabled", true);
+                    physical_bone.set(
+                        "joint_constraints/angular_limit_lower",
+                        limit.min.to_degrees() as f64,
+                    );
+                    physical_bone.set(
+                        "joint_constraints/angular_limit_upper",
+                        limit.max.to_degrees() as f64,
+                    );
+                }
+                None => physical_bone.set_joint_type(PhysicalBone::JOINT_TYPE_NONE),
+            }
+
+            let shape = CapsuleShape::new();
+            shape.set_radius(bone.radius as f64);
+            shape.set_height(capsule_height(segment.length(), bone.radius) as f64);
+            let collision_shape = CollisionShape::new();
+            if segment.length() > f32::EPSILON {
+                collision_shape.set_transform(
+                    Transform::IDENTITY.looking_at(segment / 2.0, Vector3::new(0.0, 0.0, 1.0)),
+                );
+            }
+            collision_shape.set_shape(shape);
+            physical_bone.add_child(collision_shape, false);
+            physical_bone
+        })
+        .collect()
 }
 
 fn get_animal_dimensions(skeleton: &Skeleton) -> (Vector2, Vector2) {
@@ -464,18 +1013,27 @@ pub fn create_animal(
     script: &Ref<Script>,
     animal_material: &Ref<ShaderMaterial>,
     texture_block_size: (f32, f32),
-) -> (Ref<RigidBody, Unique>, Vector3, Vector3) {
+    collider: ColliderShape,
+    style: MeshStyle,
+) -> Result<SpawnedAnimal, Vec<AnimalError>> {
+    let errors = animal.validate();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
     let animal_node = RigidBody::new();
     let animal_container = Spatial::new();
+    animal_container.set_name(CONTAINER_NAME);
 
-    let animal_skeleton = create_animal_skeleton(animal);
+    let (animal_skeleton, bones) = create_animal_skeleton(animal).map_err(|e| vec![e])?;
+    let limbs = limb_infos(animal, &bones).map_err(|e| vec![e])?;
 
-    let animal_meshes = create_animal_meshes(
-        animal,
-        &animal_skeleton,
-        animal_material,
-        texture_block_size,
-    );
+    let mesh_data =
+        build_animal_meshes(animal, &bones, texture_block_size, style).map_err(|e| vec![e])?;
+    let animal_meshes = create_animal_meshes(&mesh_data, animal_material);
+    // the meshes only say which rows of the atlas their textures are in
+    unsafe { animal_material.assume_safe() }
+        .set_shader_param("texture_block_height", texture_block_size.1);
 
     let mut min_coord = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
     let mut max_coord = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
@@ -510,27 +1068,34 @@ pub fn create_animal(
     }
     let mut size = (max_coord - min_coord) / 2.0;
     let center = (max_coord + min_coord) / 2.0;
-    size.z = 1.0;
+    size.z = HALF_DEPTH;
 
     godot_print!("{:?}, {:?}", size, center);
 
-    let shape = BoxShape::new();
-    shape.set_extents(size);
-    let collision_shape = CollisionShape::new();
-    collision_shape.translate(center);
-    collision_shape.set_shape(shape);
-
-    animal_node.add_child(collision_shape, false);
+    for collision_shape in create_collision_shapes(collider, animal, &mesh_data, size, center) {
+        animal_node.add_child(collision_shape, false);
+    }
     animal_node.set_axis_lock(4, true);
     animal_node.set_axis_lock(8, true);
     animal_node.set_axis_lock(16, true);
     animal_node.set_axis_lock(32, true);
 
+    for physical_bone in create_physical_bones(animal) {
+        animal_skeleton.add_child(physical_bone, false);
+    }
     animal_container.add_child(animal_skeleton, false);
     animal_node.add_child(animal_container, false);
     animal_node.set_script(script);
-    unsafe { script.assume_safe().set("animal_dimensions", size) }
-    (animal_node, size, center)
+    Ok(SpawnedAnimal {
+        body: animal_node,
+        info: AnimalInfo {
+            dimensions: size,
+            center,
+            limbs,
+            behavior: animal.behavior.clone(),
+            ..Default::default()
+        },
+    })
 }
 
 fn vec2(v: Vector3) -> Vector2 {
@@ -1,317 +1,644 @@
 use std::collections::HashMap;
 use std::ops::Div;
 
+use crate::animal::{AnimalInfo, LimbInfo, LimbRole, LimbSide, SpeciesInfo, CONTAINER_NAME};
+use crate::behavior::{Behavior, BehaviorParams, Senses};
+use crate::gait::GaitPlanner;
+use crate::ground::{average_height, body_tilt, place_foot, Ground};
+use crate::ik::{solver_for, IkSolver, JointLimit};
+use crate::look::{look_angles, neck_limits, turn_neck};
+use crate::secondary::{SecondaryParams, SpringChain};
+use crate::target::{BezierTarget, Easing, ParabolicTarget, SpringTarget, Target};
 use gdnative::api::*;
 use gdnative::prelude::*;
 use itertools::Itertools;
-use rand::thread_rng;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::iter::zip;
 
-trait Target {
-    fn update_target(&mut self, target: Vector2);
-    fn delta(&mut self, delta: f32);
-    fn target(&self) -> Vector2;
-    fn elapsed(&self) -> f32;
-}
-
-pub struct LinearTarget {
-    prev: Vector2,
-    curr: Vector2,
-    elapsed: f32,
-    time: f32,
-}
-
-impl LinearTarget {
-    pub fn new(target: Vector2, time: f32) -> Self {
-        Self {
-            prev: target,
-            curr: target,
-            elapsed: f32::MAX,
-            time,
-        }
-    }
-}
-
-impl Target for LinearTarget {
-    fn update_target(&mut self, target: Vector2) {
-        self.prev = self.curr;
-        self.curr = target;
-        self.elapsed = 0.0;
-    }
-
-    fn delta(&mut self, delta: f32) {
-        self.elapsed += delta;
-    }
-
-    fn target(&self) -> Vector2 {
-        if self.elapsed > self.time {
-            self.curr
-        } else {
-            self.prev
-                .linear_interpolate(self.curr, self.elapsed / self.time)
-        }
-    }
-
-    fn elapsed(&self) -> f32 {
-        self.elapsed
-    }
-}
-
-pub struct ParabolicTarget {
-    prev: Vector2,
-    curr: Vector2,
-    elapsed: f32,
-    arc_time: f32,
-    arc_slope: f32,
-}
-
-impl ParabolicTarget {
-    pub fn new(target: Vector2, arc_time: f32, arc_slope: f32) -> Self {
-        Self {
-            prev: target,
-            curr: target,
-            elapsed: f32::MAX,
-            arc_time,
-            arc_slope,
-        }
-    }
-}
-
-impl Target for ParabolicTarget {
-    fn update_target(&mut self, target: Vector2) {
-        self.prev = self.curr;
-        self.curr = target;
-        self.elapsed = 0.0;
-    }
-
-    fn delta(&mut self, delta: f32) {
-        self.elapsed += delta;
-    }
-
-    fn target(&self) -> Vector2 {
-        if self.elapsed > self.arc_time {
-            self.curr
-        } else {
-            let diff = self.curr.x - self.prev.x;
-            let x = self.elapsed / self.arc_time * diff;
-            Vector2::new(
-                x,
-                -self.arc_slope / diff.abs() * x * x + x.abs() * self.arc_slope,
-            ) + self.prev
-        }
-    }
-
-    fn elapsed(&self) -> f32 {
-        self.elapsed
-    }
-}
-
 #[derive(NativeClass)]
 #[inherit(RigidBody)]
 pub struct AnimalController {
-    targets: Vec<(String, String, usize, usize, Box<dyn Target>)>,
+    targets: Vec<(String, LimbRole, usize, usize, Box<dyn Target>)>,
+    limbs: Vec<LimbInfo>,
     bones: HashMap<String, Vec<i64>>,
+    solvers: HashMap<String, Box<dyn IkSolver>>,
+    /// limb name, first simulated bone and simulation of every chain that swings about freely
+    chains: Vec<(String, usize, SpringChain)>,
+    /// the body's velocity last frame, to work out how hard it's accelerating
+    last_velocity: Vector2,
+    /// the spine bones from the head down to the last one that turns to look at things
+    neck: Vec<i64>,
+    neck_limits: Vec<JointLimit>,
+    /// how far each joint of the neck is turned right now
+    neck_angles: Vec<f32>,
+    look_target: Option<Vector3>,
+    gait: GaitPlanner,
+    /// index into `targets` of every leg the gait planner knows about
+    gait_legs: Vec<usize>,
+    /// how far below the body the feet are when standing on flat ground
+    rest_foot_height: f32,
     time: f64,
     limb_length: f32,
-    animal_dimensions: Vector2,
-    random_next: f64,
+    /// half the size of the box around the animal's meshes
+    dimensions: Vector3,
+    /// the middle of the box around the animal's meshes, relative to the body
+    center: Vector3,
+    behavior: Behavior,
+    rng: StdRng,
+    species: SpeciesInfo,
+    /// whether the skeleton is being thrown about by physics instead of animated
+    ragdoll: bool,
+    /// how long ago the ragdoll was let go of, and where each bone was in the world when it was,
+    /// while the animation blends back in
+    ragdoll_blend: Option<(f32, Vec<(i64, Transform)>)>,
 }
 
-const NUM_IK_ITERATIONS: usize = 10;
+/// how long a step takes when it isn't part of the gait
+const STEP_TIME: f32 = 0.15;
+/// how high feet are lifted while stepping, relative to leg length
+const STEP_LIFT: f32 = 0.3;
+/// how far through a step the curve of a foot is pulled up, near the ends so feet lift off and
+/// land steeply instead of dragging along the ground
+const STEP_CONTROLS: (f32, f32) = (0.1, 0.9);
+/// roughly how long arms take to catch up with where they're swaying to
+const SWAY_TIME: f32 = 0.5;
+/// how much the length of each step varies, so the legs don't look mechanical
+const STEP_JITTER: f32 = 0.1;
+/// how far below its root a foot hangs when there's no ground under it
+const FOOT_DROP: f32 = 1.5;
+/// how quickly the body leans and sinks to follow the feet
+const TILT_SPEED: f32 = 5.0;
+/// how many joints of the spine behind the head turn to look at things
+const NECK_JOINTS: usize = 2;
+/// how quickly the head turns to look at things
+const LOOK_SPEED: f32 = 6.0;
+/// how quickly the animal gets up to the speed it wants to go at
+const ACCELERATION: f32 = 4.0;
+/// how long it takes to blend from a ragdoll back to the animation
+const RAGDOLL_BLEND_TIME: f32 = 0.5;
+
 #[methods]
 impl AnimalController {
     fn new(_: &RigidBody) -> Self {
         Self {
             targets: Vec::new(),
+            limbs: Vec::new(),
             bones: HashMap::new(),
+            solvers: HashMap::new(),
+            chains: Vec::new(),
+            last_velocity: Vector2::ZERO,
+            neck: Vec::new(),
+            neck_limits: Vec::new(),
+            neck_angles: Vec::new(),
+            look_target: None,
+            gait: GaitPlanner::new(&[], 0.0),
+            gait_legs: Vec::new(),
+            rest_foot_height: 0.0,
             limb_length: 0.0,
-            animal_dimensions: Vector2::ZERO,
+            dimensions: Vector3::ZERO,
+            center: Vector3::ZERO,
             time: 0.0,
-            random_next: 0.0,
+            behavior: Behavior::new(BehaviorParams::default()),
+            rng: StdRng::seed_from_u64(0),
+            species: SpeciesInfo::default(),
+            ragdoll: false,
+            ragdoll_blend: None,
         }
     }
 
+    /// hand over everything about the animal, before it's added to the scene tree
+    pub fn init(&mut self, info: AnimalInfo) {
+        self.dimensions = info.dimensions;
+        self.center = info.center;
+        self.limbs = info.limbs;
+        self.behavior = Behavior::new(info.behavior);
+        self.species = info.species;
+        self.rng = StdRng::seed_from_u64(info.seed);
+    }
+
     #[export]
     fn _ready(&mut self, owner: &RigidBody) {
         self.ready(owner).unwrap();
     }
 
     fn ready(&mut self, owner: &RigidBody) -> Option<()> {
-        let skeleton = unsafe {
-            owner
-                .get_child(1)?
-                .assume_safe()
-                .get_child(0)?
-                .assume_safe()
-                .cast::<Skeleton>()?
-        };
+        let skeleton = skeleton(owner)?;
 
-        let mut names: Vec<_> = ["front_leg", "back_leg"]
-            .iter()
-            .flat_map(|x| {
-                ["", "_0", "_1", "_2", "_3"]
-                    .iter()
-                    .map(move |y| format!("{}{}", x, y))
-            })
-            .collect();
+        for limb in &self.limbs {
+            self.bones.insert(limb.name.clone(), limb.bones.clone());
+        }
 
-        names.push("spine".to_string());
+        if let Some(leg) = self
+            .limbs
+            .iter()
+            .find(|x| x.role == LimbRole::Leg && !x.bones.is_empty())
+        {
+            self.limb_length = get_limb_length(leg.bones.clone(), &skeleton);
+        }
 
-        for name in &names {
-            let mut ids = Vec::new();
-            while let Some(bone_id) = get_bone_id(&skeleton, &format!("{}_{}", name, ids.len())) {
-                ids.push(bone_id);
+        for limb in &self.limbs {
+            if limb.bones.is_empty() {
+                continue;
             }
-            self.bones.insert(name.to_string(), ids);
+            let end_bone = limb.bones.len() - 1;
+            let root_posn = vec2(global_posn(&skeleton, limb.bones[0]));
+            let end_posn = vec2(global_posn(&skeleton, limb.bones[end_bone]));
+            let (start_bone, target): (usize, Box<dyn Target>) = match limb.role {
+                LimbRole::Leg => {
+                    let x = match limb.side {
+                        LimbSide::Front => 0.4,
+                        LimbSide::Center => 0.0,
+                        LimbSide::Back => -0.4,
+                    };
+                    let foot = root_posn + Vector2::new(x, -FOOT_DROP);
+                    // legs under the middle of the body hop, and hops arc higher the further they go
+                    let target: Box<dyn Target> = if limb.side == LimbSide::Center {
+                        Box::new(ParabolicTarget::new(foot, STEP_TIME, 0.2))
+                    } else {
+                        Box::new(
+                            BezierTarget::new(foot, STEP_TIME, self.limb_length * STEP_LIFT)
+                                .with_controls(STEP_CONTROLS.0, STEP_CONTROLS.1)
+                                .with_easing(Easing::EaseInOut),
+                        )
+                    };
+                    (0, target)
+                }
+                LimbRole::Arm => (0, Box::new(SpringTarget::new(end_posn, SWAY_TIME))),
+                LimbRole::Spine if end_bone < 2 => continue,
+                role => {
+                    // only the end of the spine, the tail, swings about
+                    let start_bone = if role == LimbRole::Spine {
+                        end_bone - 2
+                    } else {
+                        0
+                    };
+                    if let Some(params) = SecondaryParams::for_role(role) {
+                        let rest = rest_chain(&skeleton, &limb.bones[start_bone..]);
+                        self.chains.push((
+                            limb.name.clone(),
+                            start_bone,
+                            SpringChain::new(&rest, params),
+                        ));
+                    }
+                    continue;
+                }
+            };
+            let rest = rest_chain(&skeleton, &limb.bones[start_bone..=end_bone]);
+            self.solvers
+                .insert(limb.name.clone(), solver_for(limb.role, &rest));
+            self.targets
+                .push((limb.name.clone(), limb.role, start_bone, end_bone, target));
         }
 
-        for name in &names {
-            if name.contains("leg") && self.bones[name].len() > 0 {
-                self.limb_length = get_limb_length(self.bones["front_leg"].clone(), &skeleton);
-                break;
+        if let Some(spine) = self.limbs.iter().find(|x| x.role == LimbRole::Spine) {
+            // the neck stops before the first bone legs or arms hang off, and before the tail
+            let joints = self
+                .limbs
+                .iter()
+                .filter(|x| matches!(x.role, LimbRole::Leg | LimbRole::Arm))
+                .filter_map(|x| {
+                    let parent = skeleton.get_bone_parent(*x.bones.first()?);
+                    spine.bones.iter().position(|bone| *bone == parent)
+                })
+                .chain([NECK_JOINTS, spine.bones.len().saturating_sub(4)])
+                .min()
+                .unwrap_or(0);
+            if joints > 0 {
+                self.neck = spine.bones[..=joints].to_vec();
+                self.neck_limits = neck_limits(joints);
+                self.neck_angles = vec![0.0; joints];
             }
         }
 
-        for name in &names {
-            if name.contains("leg") && self.bones[name].len() > 0 {
-                if name.contains("front") {
-                    let front_leg_target =
-                        vec2(global_posn(&skeleton, self.bones[name][0])) + Vector2::new(0.4, -1.5);
-                    self.targets.push((
-                        name.clone(),
-                        "leg".into(),
-                        0,
-                        self.bones[name].len() - 1,
-                        Box::new(ParabolicTarget::new(front_leg_target, 0.15, 0.2)),
-                    ));
-                } else if name.contains("back") {
-                    let back_leg_target = vec2(global_posn(&skeleton, self.bones[name][0]))
-                        + Vector2::new(-0.4, -1.5);
-                    self.targets.push((
-                        name.into(),
-                        "leg".into(),
-                        0,
-                        self.bones[name].len() - 1,
-                        Box::new(ParabolicTarget::new(back_leg_target, 0.15, 0.2)),
-                    ));
-                }
+        // the ragdoll falls out of the body, not against it
+        unsafe { skeleton.physical_bones_add_collision_exception(owner.get_rid()) };
+
+        let spine = self
+            .limbs
+            .iter()
+            .find(|x| x.role == LimbRole::Spine)
+            .map_or(&[][..], |x| &x.bones[..]);
+        let mut legs = Vec::new();
+        for (i, (limb_name, role, ..)) in self.targets.iter().enumerate() {
+            if *role == LimbRole::Leg {
+                let side = self
+                    .limbs
+                    .iter()
+                    .find(|x| &x.name == limb_name)
+                    .map_or(LimbSide::Center, |x| x.side);
+                let along = spine_position(&skeleton, spine, self.bones[limb_name][0]);
+                legs.push((side, along.unwrap_or(spine.len()) as f32));
+                self.gait_legs.push(i);
             }
         }
-
-        let tail_target = vec2(global_posn(
-            &skeleton,
-            self.bones["spine"][self.bones["spine"].len() - 1],
-        ));
-        self.targets.push((
-            "spine".into(),
-            "tail".into(),
-            self.bones["spine"].len() - 3,
-            self.bones["spine"].len() - 1,
-            Box::new(LinearTarget::new(tail_target, 1.0)),
-        ));
+        self.gait = GaitPlanner::new(&legs, self.limb_length);
+        if let Some(height) = average_height(&self.feet()) {
+            self.rest_foot_height = height - owner.global_transform().origin.y;
+        }
 
         Some(())
     }
 
+    /// the name of every limb, the spine first
     #[export]
-    fn _process(&mut self, owner: &RigidBody, delta: f64) {
-        self.process(owner, delta as f32).unwrap();
-        self.time += delta;
-        //owner.add_central_force(Vector3::new(-10.0, 0.0, 0.0));
+    fn get_limb_names(&self, _owner: &RigidBody) -> Vec<String> {
+        self.limbs.iter().map(|x| x.name.clone()).collect()
     }
 
-    fn process(&mut self, owner: &RigidBody, delta: f32) -> Option<()> {
-        let skeleton = unsafe {
-            owner
-                .get_child(1)?
-                .assume_safe()
-                .get_child(0)?
-                .assume_safe()
-                .cast::<Skeleton>()?
+    /// what a limb is for, its bones from root to tip and how long they are, or an empty
+    /// dictionary if there's no limb called `name`
+    #[export]
+    fn get_limb(&self, owner: &RigidBody, name: String) -> Dictionary {
+        let dict = Dictionary::new();
+        let (limb, skeleton) = match (self.limbs.iter().find(|x| x.name == name), skeleton(owner)) {
+            (Some(limb), Some(skeleton)) => (limb, skeleton),
+            _ => return dict.into_shared(),
         };
-        let mut names: Vec<_> = ["front_leg", "back_leg"]
+        let lengths = limb
+            .bones
             .iter()
-            .flat_map(|x| {
-                ["", "_0", "_1", "_2", "_3"]
-                    .iter()
-                    .map(move |y| format!("{}{}", x, y))
+            .skip(1)
+            .map(|x| skeleton.get_bone_rest(*x).origin.length())
+            .collect::<Vec<_>>();
+        dict.insert("name", &limb.name);
+        dict.insert("role", format!("{:?}", limb.role));
+        dict.insert("side", format!("{:?}", limb.side));
+        dict.insert("bones", &limb.bones);
+        dict.insert(
+            "bone_names",
+            limb.bones
+                .iter()
+                .map(|x| skeleton.get_bone_name(*x))
+                .collect::<Vec<_>>(),
+        );
+        dict.insert("length", lengths.iter().sum::<f32>());
+        dict.insert("lengths", lengths);
+        dict.into_shared()
+    }
+
+    /// the box around the animal's meshes, relative to the body
+    #[export]
+    fn get_bounds(&self, _owner: &RigidBody) -> Dictionary {
+        let dict = Dictionary::new();
+        dict.insert("position", self.center - self.dimensions);
+        dict.insert("size", self.dimensions * 2.0);
+        dict.insert("center", self.center);
+        dict.into_shared()
+    }
+
+    /// the species the animal was blended from, with one gradient each for how much of it went into
+    /// every point of the spine, and how the blend behaves
+    #[export]
+    fn get_species(&self, _owner: &RigidBody) -> Dictionary {
+        let dict = Dictionary::new();
+        dict.insert("species", &self.species.species);
+        dict.insert("gradients", &self.species.gradients);
+        dict.insert("behavior", &self.behavior.params);
+        dict.into_shared()
+    }
+
+    /// turn the head to face `position` until told otherwise
+    #[export]
+    fn look_at(&mut self, _owner: &RigidBody, position: Vector3) {
+        self.look_target = Some(position);
+    }
+
+    /// let the head go back to facing forwards
+    #[export]
+    fn stop_looking(&mut self, _owner: &RigidBody) {
+        self.look_target = None;
+    }
+
+    /// let the animal go limp and fall about under physics, carrying on at the speed it was going
+    #[export]
+    fn enable_ragdoll(&mut self, owner: &RigidBody) {
+        let skeleton = match skeleton(owner) {
+            Some(skeleton) if !self.ragdoll => skeleton,
+            _ => return,
+        };
+        self.ragdoll = true;
+        self.ragdoll_blend = None;
+        skeleton.clear_bones_global_pose_override();
+
+        // the body stays where it is while the bones do the falling
+        let velocity = owner.linear_velocity();
+        owner.set_mode(RigidBody::MODE_KINEMATIC);
+        skeleton.physical_bones_start_simulation(VariantArray::new_shared());
+        for bone in skeleton
+            .get_children()
+            .iter()
+            .filter_map(|x| x.to_object::<PhysicalBone>())
+        {
+            let bone = unsafe { bone.assume_safe() };
+            bone.apply_central_impulse(velocity * bone.mass() as f32);
+        }
+    }
+
+    /// take back control from the ragdoll. the body moves to wherever the head ended up, and the
+    /// bones blend from how they fell back into the animation
+    #[export]
+    fn disable_ragdoll(&mut self, owner: &RigidBody) {
+        let skeleton = match skeleton(owner) {
+            Some(skeleton) if self.ragdoll => skeleton,
+            _ => return,
+        };
+        self.ragdoll = false;
+        let fallen = (0..skeleton.get_bone_count())
+            .map(|x| {
+                (
+                    x,
+                    skeleton.global_transform() * skeleton.get_bone_global_pose(x),
+                )
             })
-            .collect();
+            .collect::<Vec<_>>();
+        skeleton.physical_bones_stop_simulation();
+
+        if let Some(head) = self
+            .limbs
+            .iter()
+            .find(|x| x.role == LimbRole::Spine)
+            .and_then(|x| x.bones.first())
+        {
+            let moved = fallen[*head as usize].1.origin - global_posn(&skeleton, *head);
+            owner.global_translate(Vector3::new(moved.x, moved.y, 0.0));
+        }
+        // animals are always rigid bodies, held upright by their locked axes
+        owner.set_mode(RigidBody::MODE_RIGID);
+        owner.set_linear_velocity(Vector3::ZERO);
+        self.last_velocity = Vector2::ZERO;
+        self.ragdoll_blend = Some((0.0, fallen));
+    }
 
-        names.push("spine".to_string());
+    #[export]
+    fn _process(&mut self, owner: &RigidBody, delta: f64) {
+        self.process(owner, delta as f32).unwrap();
+        self.time += delta;
+        //owner.add_central_force(Vector3::new(-10.0, 0.0, 0.0));
+    }
 
-        let mut bones = HashMap::new();
-        for name in names {
-            let mut ids = Vec::new();
-            while let Some(bone_id) = get_bone_id(&skeleton, &format!("{}_{}", name, ids.len())) {
-                ids.push(bone_id);
-            }
-            bones.insert(name.to_string(), ids);
+    fn process(&mut self, owner: &RigidBody, delta: f32) -> Option<()> {
+        let skeleton = skeleton(owner)?;
+        if self.ragdoll {
+            return Some(());
         }
+        self.turn_head(&skeleton, delta);
 
         let mut reached_end = Vec::new();
         for (limb_name, _, start_bone, end_bone, target) in &self.targets {
             reached_end.push(inverse_kinematics(
                 &skeleton,
-                &bones,
+                &self.bones,
                 limb_name,
                 target.target(),
                 *start_bone,
                 *end_bone,
+                self.solvers[limb_name].as_ref(),
             ));
         }
 
-        for (i, (limb_name, limb_type, start_bone, end_bone, target)) in
+        let ground = PhysicsGround::new(owner);
+        for step in self.gait.tick(delta, owner.linear_velocity().x) {
+            let (limb_name, _, start_bone, _, target) = &mut self.targets[self.gait_legs[step.leg]];
+            let limb_root_posn = vec2(global_posn(&skeleton, self.bones[limb_name][*start_bone]));
+            target.set_time(step.duration);
+            target.update_target(place_foot(
+                &ground,
+                limb_root_posn,
+                step.reach * self.rng.gen_range(1.0 - STEP_JITTER..1.0 + STEP_JITTER),
+                self.limb_length,
+                FOOT_DROP,
+            ));
+        }
+
+        for (i, (limb_name, role, start_bone, end_bone, target)) in
             self.targets.iter_mut().enumerate()
         {
             let limb_root_posn = vec2(global_posn(&skeleton, self.bones[limb_name][*start_bone]));
-            if !reached_end[i] && limb_type == "leg" {
-                let x_diff = self.animal_dimensions.x * 0.3;
-                let y = (self.limb_length * self.limb_length - x_diff * x_diff).sqrt();
-                //let mut rng = rand::thread_rng();
-                //let r = rng.gen::<f32>() - 0.5;
-                if target.target().x < limb_root_posn.x {
-                    target.update_target(limb_root_posn + Vector2::new(0.8, -1.5));
-                } else {
-                    target.update_target(limb_root_posn + Vector2::new(-0.8, -1.5));
-                }
+            // a foot that got left behind out of reach steps back under the body
+            if !reached_end[i] && *role == LimbRole::Leg && target.done() {
+                target.set_time(STEP_TIME);
+                target.update_target(place_foot(
+                    &ground,
+                    limb_root_posn,
+                    0.0,
+                    self.limb_length,
+                    FOOT_DROP,
+                ));
             }
-            if limb_type == "tail" {
-                target.update_target(
-                    vec2(global_posn(&skeleton, self.bones[limb_name][*start_bone]))
-                        + Vector2::new(5.0, self.time.sin() as f32),
-                );
-
-                /*
-                if target.elapsed() > 1.5 {
-                    let mut rng = rand::thread_rng();
-                    let pose = skeleton.get_bone_pose(self.bones[limb_name][*start_bone]);
-                    skeleton.set_bone_pose(self.bones[limb_name][*start_bone], rotate(0.0));
-
-
-                    let angle  = rng.gen::<f32>() - 0.5 + 3.1415;
-                    target.update_target(
-                        vec2(global_posn(&skeleton, self.bones[limb_name][*start_bone]))
-                            - Vector2::new(angle.cos(), angle.sin()) * 2.0,
-                    );
-                    skeleton.set_bone_pose(self.bones[limb_name][*start_bone], pose);
-                }
-                */
+            if *role == LimbRole::Arm {
+                let reach = rest_reach(&skeleton, &self.bones[limb_name][*start_bone..=*end_bone]);
+                target.update_target(limb_root_posn + reach.rotated(self.time.sin() as f32 * 0.3));
             }
             target.delta(delta);
         }
 
-        if self.time > self.random_next {
-            let mut rng = rand::thread_rng();
-            owner.set_axis_velocity(Vector3::new((rng.gen::<f32>() - 0.5) * 12.0, 0.0, 0.0));
-            self.random_next = self.time + rng.gen::<f64>() * 5.0;
+        let velocity = vec2(owner.linear_velocity());
+        let acceleration = if delta > 0.0 {
+            (velocity - self.last_velocity) / delta
+        } else {
+            Vector2::ZERO
+        };
+        self.last_velocity = velocity;
+        for (limb_name, start_bone, chain) in &mut self.chains {
+            let bones = &self.bones[limb_name][*start_bone..];
+            let rest = rest_chain(&skeleton, bones);
+            // the rest pose turns with whatever the chain hangs off
+            let angle = parent_angle(&skeleton, bones[0]);
+            let turned_rest = rest.iter().map(|x| x.rotated(angle)).collect::<Vec<_>>();
+            chain.step(delta, &turned_rest, acceleration);
+            pose_chain(&skeleton, bones, &rest, chain.joints());
+        }
+
+        self.follow_feet(owner, delta);
+        self.blend_from_ragdoll(&skeleton, delta);
+
+        let senses = Senses {
+            position: owner.global_transform().origin.x,
+            threats: group_positions(owner, "threats"),
+            food: group_positions(owner, "food"),
+            herd: group_positions(owner, "animals"),
+        };
+        let speed = self.behavior.tick(delta, &senses, &mut self.rng);
+        let velocity = owner.linear_velocity();
+        owner.set_linear_velocity(Vector3::new(
+            velocity.x + (speed - velocity.x) * (delta * ACCELERATION).min(1.0),
+            velocity.y,
+            velocity.z,
+        ));
+
+        Some(())
+    }
+
+    /// turn the neck a little further towards wherever the animal is looking. the neck is only
+    /// ever turned from its rest pose, so the body and legs underneath carry on as usual
+    fn turn_head(&mut self, skeleton: &Skeleton, delta: f32) {
+        if self.neck.is_empty() {
+            return;
+        }
+        let head = vec2(skeleton.get_bone_rest(self.neck[0]).origin);
+        let neck = rest_chain(skeleton, &self.neck)
+            .iter()
+            .map(|x| *x + head)
+            .collect::<Vec<_>>();
+        let wanted = match self.look_target {
+            Some(target) => {
+                let target = skeleton.global_transform().affine_inverse().xform(target);
+                look_angles(&neck, vec2(target), &self.neck_limits)
+            }
+            None => vec![0.0; self.neck_angles.len()],
+        };
+        let weight = (delta * LOOK_SPEED).min(1.0);
+        for (angle, wanted) in zip(&mut self.neck_angles, wanted) {
+            *angle += (wanted - *angle) * weight;
+        }
+
+        // the head turns and moves around the neck, and each joint after it turns back the other
+        // way so the body stays put
+        let posed = turn_neck(&neck, &self.neck_angles);
+        let offset = posed[0] - neck[0];
+        skeleton.set_bone_pose(
+            self.neck[0],
+            Transform {
+                origin: Vector3::new(offset.x, offset.y, 0.0),
+                ..rotate(self.neck_angles.iter().sum())
+            },
+        );
+        for (bone_idx, angle) in zip(&self.neck[1..], &self.neck_angles) {
+            skeleton.set_bone_pose(*bone_idx, rotate(-angle));
         }
+    }
 
+    /// lay the pose the ragdoll fell into over the animation, a little less every frame
+    fn blend_from_ragdoll(&mut self, skeleton: &Skeleton, delta: f32) {
+        let (elapsed, fallen) = match &mut self.ragdoll_blend {
+            Some(blend) => blend,
+            None => return,
+        };
+        *elapsed += delta;
+        if *elapsed >= RAGDOLL_BLEND_TIME {
+            skeleton.clear_bones_global_pose_override();
+            self.ragdoll_blend = None;
+            return;
+        }
+        // eased so the animation takes over gently instead of with a jolt at either end
+        let amount = 1.0 - Easing::Smoothstep.apply(*elapsed / RAGDOLL_BLEND_TIME);
+        let to_skeleton = skeleton.global_transform().affine_inverse();
+        for (bone_idx, pose) in fallen.iter() {
+            skeleton.set_bone_global_pose_override(
+                *bone_idx,
+                to_skeleton * *pose,
+                amount as f64,
+                true,
+            );
+        }
+    }
+
+    /// where every foot is headed
+    fn feet(&self) -> Vec<Vector2> {
+        self.gait_legs
+            .iter()
+            .map(|i| self.targets[*i].4.target())
+            .collect()
+    }
+
+    /// lean the body with the slope under the feet and sink or rise with them
+    fn follow_feet(&self, owner: &RigidBody, delta: f32) -> Option<()> {
+        let feet = self.feet();
+        let height = average_height(&feet)?;
+        let container = container(owner)?;
+        let offset = (height - owner.global_transform().origin.y - self.rest_foot_height)
+            .clamp(-self.limb_length * 0.5, self.limb_length * 0.5);
+        let weight = (delta * TILT_SPEED).min(1.0);
+
+        let rotation = container.rotation();
+        container.set_rotation(Vector3::new(
+            0.0,
+            0.0,
+            rotation.z + (body_tilt(&feet) - rotation.z) * weight,
+        ));
+        let translation = container.translation();
+        container.set_translation(Vector3::new(
+            translation.x,
+            translation.y + (offset - translation.y) * weight,
+            translation.z,
+        ));
         Some(())
     }
 }
 
+/// what holds an animal's skeleton, leaning and moving it to follow the feet
+fn container(owner: &RigidBody) -> Option<TRef<'_, Spatial>> {
+    unsafe {
+        owner
+            .get_node(CONTAINER_NAME)?
+            .assume_safe()
+            .cast::<Spatial>()
+    }
+}
+
+/// the skeleton every animal has inside its container
+fn skeleton(owner: &RigidBody) -> Option<TRef<'_, Skeleton>> {
+    unsafe {
+        container(owner)?
+            .get_child(0)?
+            .assume_safe()
+            .cast::<Skeleton>()
+    }
+}
+
+/// x positions of every node in a group, apart from the animal itself
+fn group_positions(owner: &RigidBody, group: &str) -> Vec<f32> {
+    let tree = match owner.get_tree() {
+        Some(tree) => tree,
+        None => return Vec::new(),
+    };
+    let nodes = unsafe { tree.assume_safe().get_nodes_in_group(group) };
+    nodes
+        .iter()
+        .filter_map(|x| x.to_object::<Spatial>())
+        .map(|x| unsafe { x.assume_safe() })
+        .filter(|x| x.get_instance_id() != owner.get_instance_id())
+        .map(|x| x.global_transform().origin.x)
+        .collect()
+}
+
+/// raycasts against everything in the physics world apart from the animal itself
+struct PhysicsGround {
+    space: Option<Ref<PhysicsDirectSpaceState>>,
+    exclude: VariantArray,
+}
+
+impl PhysicsGround {
+    fn new(owner: &RigidBody) -> Self {
+        let space = owner
+            .get_world()
+            .and_then(|world| unsafe { world.assume_safe().direct_space_state() });
+        let exclude = VariantArray::new();
+        exclude.push(owner.get_rid());
+        Self {
+            space,
+            exclude: exclude.into_shared(),
+        }
+    }
+}
+
+impl Ground for PhysicsGround {
+    fn raycast_down(&self, from: Vector2, depth: f32) -> Option<Vector2> {
+        let space = unsafe { self.space.as_ref()?.assume_safe() };
+        let hit = space.intersect_ray(
+            Vector3::new(from.x, from.y, 0.0),
+            Vector3::new(from.x, from.y - depth, 0.0),
+            self.exclude.new_ref(),
+            0x7FFF_FFFF,
+            true,
+            false,
+        );
+        let position = Vector3::from_variant(&hit.get("position")?).ok()?;
+        Some(vec2(position))
+    }
+}
+
 fn rotate(angle: f32) -> Transform {
     Transform {
         basis: Basis::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), angle),
@@ -323,6 +650,37 @@ fn global_posn(skeleton: &Skeleton, bone_idx: i64) -> Vector3 {
     (skeleton.global_transform() * skeleton.get_bone_global_pose(bone_idx)).origin
 }
 
+/// how many bones down `spine` from the head the limb starting at `bone_idx` hangs off
+fn spine_position(skeleton: &Skeleton, spine: &[i64], bone_idx: i64) -> Option<usize> {
+    let mut bone_idx = skeleton.get_bone_parent(bone_idx);
+    while bone_idx >= 0 {
+        if let Some(i) = spine.iter().position(|x| *x == bone_idx) {
+            return Some(i);
+        }
+        bone_idx = skeleton.get_bone_parent(bone_idx);
+    }
+    None
+}
+
+/// the joints of a chain of bones at rest, relative to the first one
+fn rest_chain(skeleton: &Skeleton, chain: &[i64]) -> Vec<Vector2> {
+    let mut current_posn = Vector2::ZERO;
+    let mut positions = vec![current_posn];
+    for bone_idx in chain.iter().skip(1) {
+        current_posn += vec2(skeleton.get_bone_rest(*bone_idx).origin);
+        positions.push(current_posn);
+    }
+    positions
+}
+
+/// where the tip of a chain sits relative to its root when nothing is bent
+fn rest_reach(skeleton: &Skeleton, chain: &[i64]) -> Vector2 {
+    rest_chain(skeleton, chain)
+        .last()
+        .copied()
+        .unwrap_or(Vector2::ZERO)
+}
+
 fn get_limb_length(limb: Vec<i64>, skeleton: &Skeleton) -> f32 {
     let mut length = 0.0;
     for bone_idx in &limb {
@@ -339,86 +697,48 @@ fn inverse_kinematics(
     target: Vector2,
     start_bone: usize,
     end_bone: usize,
+    solver: &dyn IkSolver,
 ) -> bool {
-    let limb = bones[limb_name].clone();
+    let limb = &bones[limb_name];
     let limb_posn =
         (skeleton.global_transform() * skeleton.get_bone_global_pose(limb[start_bone])).origin;
     let target = Vector2::new(target.x - limb_posn.x, target.y - limb_posn.y);
 
-    let mut bone_positions = Vec::new();
-    let mut current_posn = Vector3::ZERO;
-    bone_positions.push(current_posn);
-    for bone_idx in &limb[(start_bone + 1)..(end_bone + 1)] {
-        current_posn += skeleton.get_bone_rest(*bone_idx).origin;
-        bone_positions.push(current_posn);
-    }
-
+    let bone_positions = rest_chain(skeleton, &limb[start_bone..=end_bone]);
     let mut ik_bone_positions = bone_positions.clone();
-    for _ in 0..NUM_IK_ITERATIONS {
-        let last_index = ik_bone_positions.len() - 1;
-        ik_bone_positions[last_index] = Vector3::new(target.x, target.y, 0.0);
-
-        for i in (0..(ik_bone_positions.len() - 1)).rev() {
-            let dist = bone_positions[i].distance_to(bone_positions[i + 1]);
-            ik_bone_positions[i] = (ik_bone_positions[i] - ik_bone_positions[i + 1]).normalized()
-                * dist
-                + ik_bone_positions[i + 1];
-        }
-
-        ik_bone_positions[0] = Vector3::ZERO;
-
-        for i in 1..ik_bone_positions.len() {
-            let dist = bone_positions[i].distance_to(bone_positions[i - 1]);
-            ik_bone_positions[i] = (ik_bone_positions[i] - ik_bone_positions[i - 1]).normalized()
-                * dist
-                + ik_bone_positions[i - 1];
-        }
-    }
+    let reached = solver.solve(&mut ik_bone_positions, target);
+
+    pose_chain(
+        skeleton,
+        &limb[start_bone..=end_bone],
+        &bone_positions,
+        &ik_bone_positions,
+    );
+    reached
+}
 
-    let mut curr_angle = (skeleton.global_transform()
-        * skeleton.get_bone_global_pose(skeleton.get_bone_parent(limb[start_bone])))
+/// the anticlockwise angle the parent of `bone_idx` is turned by
+fn parent_angle(skeleton: &Skeleton, bone_idx: i64) -> f32 {
+    (skeleton.global_transform()
+        * skeleton.get_bone_global_pose(skeleton.get_bone_parent(bone_idx)))
     .basis
     .to_euler()
-    .z;
+    .z
+}
 
-    for (i, ((rest_start, rest_end), (pose_start, pose_end))) in zip(
-        bone_positions.iter().tuple_windows(),
-        ik_bone_positions.iter().tuple_windows(),
-    )
-    .enumerate()
+/// turn each bone of `chain` so its joints go from where they are in `rest` to where they are in
+/// `posed`. `rest` is relative to the chain's parent, `posed` is in the same directions as the world
+fn pose_chain(skeleton: &Skeleton, chain: &[i64], rest: &[Vector2], posed: &[Vector2]) {
+    let mut curr_angle = parent_angle(skeleton, chain[0]);
+    for (i, ((rest_start, rest_end), (pose_start, pose_end))) in
+        zip(rest.iter().tuple_windows(), posed.iter().tuple_windows()).enumerate()
     {
-        /*
-        unsafe {
-            if let Some(debug_draw) = autoload::<Node>("DebugDraw") {
-                debug_draw.call(
-                    "draw_line_3d",
-                    &[
-                        (*pose_end + limb_posn + Vector3::new(0.0, 0.0, -2.0)).to_variant(),
-                        (*pose_start + limb_posn + Vector3::new(0.0, 0.0, -2.0)).to_variant(),
-                        Color::from_rgb(1.0, 0.0, 0.0).to_variant(),
-                    ],
-                );
-            }
-        }
-        */
-
-        let rest = vec2(*rest_end - *rest_start);
-        let pose = vec2(*pose_end - *pose_start);
+        let rest = *rest_end - *rest_start;
+        let pose = *pose_end - *pose_start;
         let angle = -pose.angle() + rest.angle();
-        skeleton.set_bone_pose(limb[i + start_bone], rotate(angle - curr_angle));
+        skeleton.set_bone_pose(chain[i], rotate(angle - curr_angle));
         curr_angle = angle;
     }
-
-    vec2(ik_bone_positions[ik_bone_positions.len() - 1]).distance_squared_to(target) < 0.01
-}
-
-fn get_bone_id(skeleton: &TRef<Skeleton>, name: &str) -> Option<i64> {
-    for i in 0..skeleton.get_bone_count() {
-        if skeleton.get_bone_name(i).to_string() == name {
-            return Some(i);
-        }
-    }
-    None
 }
 
 fn vec2(v: Vector3) -> Vector2 {
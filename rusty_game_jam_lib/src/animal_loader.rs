@@ -0,0 +1,138 @@
+use crate::animal::Animal;
+use gdnative::api::{Directory, File};
+use gdnative::core_types::GodotError;
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(test)]
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// something went wrong while reading a creature file
+#[derive(Debug)]
+pub enum LoadError {
+    #[cfg(test)]
+    Io(PathBuf, std::io::Error),
+    Godot(PathBuf, GodotError),
+    Ron(PathBuf, ron::error::SpannedError),
+    #[cfg(test)]
+    RonWrite(PathBuf, ron::Error),
+    Json(PathBuf, serde_json::Error),
+    UnknownFormat(PathBuf),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(test)]
+            LoadError::Io(path, e) => write!(f, "couldn't read '{}': {}", path.display(), e),
+            LoadError::Godot(path, e) => write!(f, "couldn't read '{}': {}", path.display(), e),
+            LoadError::Ron(path, e) => write!(f, "couldn't parse '{}': {}", path.display(), e),
+            #[cfg(test)]
+            LoadError::RonWrite(path, e) => write!(f, "couldn't write '{}': {}", path.display(), e),
+            LoadError::Json(path, e) => write!(f, "couldn't parse '{}': {}", path.display(), e),
+            LoadError::UnknownFormat(path) => {
+                write!(f, "'{}' is not a .ron or .json file", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+fn is_creature_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|x| x.to_str()),
+        Some("ron") | Some("json")
+    )
+}
+
+/// parse a creature from the contents of the file at `path`, the format is picked from the file
+/// extension
+pub fn parse_animal(path: &Path, source: &str) -> Result<Animal, LoadError> {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("ron") => ron::from_str(source).map_err(|e| LoadError::Ron(path.to_path_buf(), e)),
+        Some("json") => {
+            serde_json::from_str(source).map_err(|e| LoadError::Json(path.to_path_buf(), e))
+        }
+        _ => Err(LoadError::UnknownFormat(path.to_path_buf())),
+    }
+}
+
+/// load every creature file in a godot directory such as `res://animals`, keyed by file name
+/// (`fox.ron` -> `fox`). goes through godot's filesystem, so it also finds the files packed into
+/// an exported game
+pub fn load_project_animals(dir: &str) -> Result<HashMap<String, Animal>, LoadError> {
+    let godot_error = |path: &str, e| LoadError::Godot(PathBuf::from(path), e);
+    let directory = Directory::new();
+    directory.open(dir).map_err(|e| godot_error(dir, e))?;
+    directory
+        .list_dir_begin(true, true)
+        .map_err(|e| godot_error(dir, e))?;
+    let mut names = Vec::new();
+    loop {
+        let name = directory.get_next().to_string();
+        if name.is_empty() {
+            break;
+        }
+        if !directory.current_is_dir() && is_creature_file(Path::new(&name)) {
+            names.push(name);
+        }
+    }
+    directory.list_dir_end();
+
+    let mut animals = HashMap::new();
+    for name in names {
+        // godot paths always use forward slashes, whatever the platform
+        let path = format!("{}/{}", dir.trim_end_matches('/'), name);
+        let file = File::new();
+        file.open(path.as_str(), File::READ)
+            .map_err(|e| godot_error(&path, e))?;
+        let source = file.get_as_text().to_string();
+        file.close();
+        let path = PathBuf::from(path);
+        if let Some(name) = path.file_stem().and_then(|x| x.to_str()) {
+            animals.insert(name.to_string(), parse_animal(&path, &source)?);
+        }
+    }
+    Ok(animals)
+}
+
+/// load a single creature straight from disk
+#[cfg(test)]
+pub fn load_animal(path: &Path) -> Result<Animal, LoadError> {
+    let source = fs::read_to_string(path).map_err(|e| LoadError::Io(path.to_path_buf(), e))?;
+    parse_animal(path, &source)
+}
+
+/// load every creature file in a directory on disk, keyed by file name. only sees real
+/// directories, the game uses `load_project_animals` instead
+#[cfg(test)]
+pub fn load_animals(dir: &Path) -> Result<HashMap<String, Animal>, LoadError> {
+    let mut animals = HashMap::new();
+    let entries = fs::read_dir(dir).map_err(|e| LoadError::Io(dir.to_path_buf(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| LoadError::Io(dir.to_path_buf(), e))?
+            .path();
+        if !is_creature_file(&path) {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|x| x.to_str()) {
+            animals.insert(name.to_string(), load_animal(&path)?);
+        }
+    }
+    Ok(animals)
+}
+
+/// write a creature out in the same format the loader reads
+#[cfg(test)]
+pub fn save_animal(animal: &Animal, path: &Path) -> Result<(), LoadError> {
+    let source = match path.extension().and_then(|x| x.to_str()) {
+        Some("ron") => ron::ser::to_string_pretty(animal, ron::ser::PrettyConfig::default())
+            .map_err(|e| LoadError::RonWrite(path.to_path_buf(), e))?,
+        Some("json") => serde_json::to_string_pretty(animal)
+            .map_err(|e| LoadError::Json(path.to_path_buf(), e))?,
+        _ => return Err(LoadError::UnknownFormat(path.to_path_buf())),
+    };
+    fs::write(path, source).map_err(|e| LoadError::Io(path.to_path_buf(), e))
+}
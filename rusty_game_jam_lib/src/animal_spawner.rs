@@ -1,10 +1,24 @@
 use crate::animal::blend_animals;
 use crate::animal::create_animal;
+use crate::animal::Animal;
 use crate::animal::BodyGradient;
-use crate::animal_templates;
+use crate::animal::GradientPreset;
+use crate::animal::SpawnedAnimal;
+use crate::animal::SpeciesInfo;
+use crate::animal_controller::AnimalController;
+use crate::animal_loader::load_project_animals;
+use crate::collider::ColliderShape;
+use crate::genetics::{breed, MutationRates};
+use crate::limb_mesh::{LimbShape, MeshStyle, Outline, MAX_TEXTURE_ROWS};
 use crate::prop_ref::*;
 use gdnative::api::*;
 use gdnative::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// large odd constant the spawn count is multiplied by to spread it over every bit of the seed
+const SEED_MIX: u64 = 0x9E37_79B9_7F4A_7C15;
 
 #[derive(NativeClass)]
 #[inherit(Spatial)]
@@ -17,6 +31,40 @@ pub struct AnimalSpawner {
     texture_block_size_y: f32,
     #[property]
     animal_script: PropRef<Script>,
+    #[property]
+    animal_directory: String,
+    /// names of the creature files to blend together
+    #[property]
+    species: Vec<String>,
+    /// one gradient per species, only used when `gradient_preset` is "custom"
+    #[property]
+    gradients: Vec<Vec<f32>>,
+    /// "custom", "even", "linear" or "random"
+    #[property]
+    gradient_preset: String,
+    /// "box", "capsules", "capsules_and_legs" or "hull"
+    #[property]
+    collider: String,
+    /// how many sides each limb's tube has, or 0 for flat ribbons that only look right from the
+    /// front
+    #[property]
+    tube_sides: i64,
+    /// how many pieces each segment of a limb is cut into to follow a smooth curve, or 0 for
+    /// straight segments with corners between them
+    #[property]
+    outline_subdivisions: i64,
+    #[property]
+    spawn_count: i64,
+    /// animals are dropped in at random points in this rectangle
+    #[property]
+    spawn_area: Rect2,
+    /// seeds the blending and spawning, and every animal's own randomness
+    #[property]
+    seed: i64,
+    animals: HashMap<String, Animal>,
+    rng: StdRng,
+    /// how many animals have been spawned so far, each one gets its own seed
+    spawned: u64,
 }
 
 #[methods]
@@ -27,32 +75,214 @@ impl AnimalSpawner {
             animal_script: None,
             texture_block_size_x: 1.0,
             texture_block_size_y: 1.0,
+            animal_directory: "res://animals".to_string(),
+            species: vec!["turtle".to_string(), "fox".to_string()],
+            gradients: vec![
+                vec![1.0, 1.0, 0.75, 0.2, 0.6, 0.1, 0.0, 0.0],
+                vec![0.0, 0.0, 0.25, 1.0, 0.4, 0.9, 1.0, 1.0],
+            ],
+            gradient_preset: "custom".to_string(),
+            collider: "box".to_string(),
+            tube_sides: 0,
+            outline_subdivisions: 0,
+            spawn_count: 1,
+            spawn_area: Rect2::new(Vector2::new(0.0, 2.0), Vector2::ZERO),
+            seed: 0,
+            animals: HashMap::new(),
+            rng: StdRng::seed_from_u64(0),
+            spawned: 0,
         }
     }
 
     #[export]
     fn _ready(&mut self, owner: &Spatial) {
+        self.rng = StdRng::seed_from_u64(self.seed as u64);
 
-        let gradients = [
-            BodyGradient(vec![1.0, 1.0, 0.75, 0.2, 0.6, 0.1, 0.0, 0.0]),
-            BodyGradient(vec![0.0, 0.0, 0.25, 1.0, 0.4, 0.9, 1.0, 1.0]),
-        ];
+        self.animals = match load_project_animals(&self.animal_directory) {
+            Ok(animals) => animals,
+            Err(e) => {
+                godot_error!("{}", e);
+                return;
+            }
+        };
+
+        for _ in 0..self.spawn_count {
+            let position = self.spawn_area.position
+                + Vector2::new(
+                    self.spawn_area.size.x * self.rng.gen::<f32>(),
+                    self.spawn_area.size.y * self.rng.gen::<f32>(),
+                );
+            self.spawn_animal(owner, Vector3::new(position.x, position.y, 0.0));
+        }
+    }
 
-        let combined = [animal_templates::turtle(), animal_templates::fox()]
-            .into_iter()
-            .zip(gradients)
+    /// blend the configured species together, with fresh gradients if a preset is used
+    fn blend(&mut self) -> Result<(Animal, SpeciesInfo), String> {
+        let mut animals = Vec::new();
+        for name in &self.species {
+            match self.animals.get(name) {
+                Some(animal) => animals.push(animal.clone()),
+                None => {
+                    return Err(format!(
+                        "no animal called '{}' in {}",
+                        name, self.animal_directory
+                    ))
+                }
+            }
+        }
+
+        let lens = animals
+            .iter()
+            .map(|x| x.body.body.len())
             .collect::<Vec<_>>();
+        let gradients = match self.gradient_preset.as_str() {
+            "custom" => self
+                .gradients
+                .iter()
+                .map(|x| BodyGradient(x.clone()))
+                .collect(),
+            name => GradientPreset::from_name(name)
+                .unwrap_or_else(|| {
+                    godot_error!("no gradient preset called '{}', using 'even'", name);
+                    GradientPreset::Even
+                })
+                .gradients(&lens, &mut self.rng),
+        };
+        if gradients.len() != animals.len() {
+            return Err(format!(
+                "{} species but {} gradients",
+                animals.len(),
+                gradients.len()
+            ));
+        }
 
-        let (animal, size, center) = create_animal(
-            &blend_animals(&combined), /*&animal_templates::fox(), */ /*&animal_templates::chicken()*/ /*&animal_templates::turtle(),  &blend_animals(&combined) */
+        let info = SpeciesInfo {
+            species: self.species.clone(),
+            gradients: gradients.iter().map(|x| x.0.clone()).collect(),
+        };
+        let combined = animals.into_iter().zip(gradients).collect::<Vec<_>>();
+        let blended =
+            blend_animals(&combined).map_err(|e| format!("couldn't blend animals: {}", e))?;
+        Ok((blended, info))
+    }
+
+    /// spawn a new creature with its feet at `position`, returns null if it couldn't be created
+    #[export]
+    fn spawn_animal(&mut self, owner: &Spatial, position: Vector3) -> Option<Ref<RigidBody>> {
+        let (blended, species) = match self.blend() {
+            Ok(blended) => blended,
+            Err(e) => {
+                godot_error!("{}", e);
+                return None;
+            }
+        };
+        self.spawn(owner, &blended, species, position)
+    }
+
+    /// breed a child of the creature files `a` and `b` and spawn it with its feet at `position`.
+    /// the same seed always breeds the same child, returns null if it couldn't be created
+    #[export]
+    fn breed(
+        &mut self,
+        owner: &Spatial,
+        a: String,
+        b: String,
+        seed: i64,
+        position: Vector3,
+    ) -> Option<Ref<RigidBody>> {
+        let (parent_a, parent_b) = match (self.animals.get(&a), self.animals.get(&b)) {
+            (Some(parent_a), Some(parent_b)) => (parent_a, parent_b),
+            _ => {
+                godot_error!(
+                    "no animal called '{}' or '{}' in {}",
+                    a,
+                    b,
+                    self.animal_directory
+                );
+                return None;
+            }
+        };
+        // every row of the atlas can be mutated into, up to as many as a point can address
+        let rows = (1.0 / self.texture_block_size_y.max(f32::EPSILON)).round() as usize;
+        let rates = MutationRates::new(rows.clamp(1, MAX_TEXTURE_ROWS));
+        let (child, gradients) = match breed(parent_a, parent_b, &rates, seed as u64) {
+            Ok(child) => child,
+            Err(e) => {
+                godot_error!("couldn't breed '{}' and '{}': {}", a, b, e);
+                return None;
+            }
+        };
+        let species = SpeciesInfo {
+            species: vec![a, b],
+            gradients: gradients.into_iter().map(|x| x.0).collect(),
+        };
+        self.spawn(owner, &child, species, position)
+    }
+
+    /// build the nodes for `animal` and add it to the scene with its feet at `position`
+    fn spawn(
+        &mut self,
+        owner: &Spatial,
+        animal: &Animal,
+        species: SpeciesInfo,
+        position: Vector3,
+    ) -> Option<Ref<RigidBody>> {
+        let collider = ColliderShape::from_name(&self.collider).unwrap_or_else(|| {
+            godot_error!("no collider called '{}', using a box", self.collider);
+            ColliderShape::Box
+        });
+        let shape = match self.tube_sides {
+            sides if sides <= 0 => LimbShape::Ribbon,
+            // anything less than a triangle isn't a tube
+            sides => LimbShape::Tube {
+                sides: sides.max(3) as usize,
+            },
+        };
+        let outline = match self.outline_subdivisions {
+            subdivisions if subdivisions <= 0 => Outline::Straight,
+            subdivisions => Outline::Spline {
+                subdivisions: subdivisions as usize,
+            },
+        };
+        let SpawnedAnimal { body, mut info } = match create_animal(
+            animal,
             get_prop(&self.animal_script),
             get_prop(&self.material),
             (self.texture_block_size_x, self.texture_block_size_y),
-        );
-    
+            collider,
+            MeshStyle { shape, outline },
+        ) {
+            Ok(animal) => animal,
+            Err(errors) => {
+                for e in errors {
+                    godot_error!("couldn't create animal: {}", e);
+                }
+                return None;
+            }
+        };
+
+        body.translate(position + Vector3::new(0.0, info.dimensions.y, 0.0));
+        body.add_to_group("animals", false);
+        info.species = species;
+        info.seed = self.seed as u64 ^ self.spawned.wrapping_mul(SEED_MIX);
+        self.spawned += 1;
 
-        animal.translate(Vector3::new(0.0, size.y + 2.0, 0.0));
+        let animal = match body.try_cast_instance::<AnimalController>() {
+            Ok(animal) => animal,
+            Err(body) => {
+                godot_error!("the animal script has to be an AnimalController");
+                body.free();
+                return None;
+            }
+        };
+        if animal.map_mut(|x, _| x.init(info)).is_err() {
+            godot_error!("couldn't set up the animal controller");
+            animal.free();
+            return None;
+        }
 
+        let animal = animal.into_base().into_shared();
         owner.add_child(animal, false);
+        Some(animal)
     }
 }
@@ -3,65 +3,95 @@ use gdnative::core_types::{Vector2, Vector3};
 use crate::animal::Animal;
 use crate::animal::BodyPoint;
 use crate::animal::Limb;
+use crate::behavior::BehaviorParams;
 
 pub fn chicken() -> Animal {
-    let texture_indices = [Some((0, 1.0)), None];
+    let texture_indices = vec![(0, 1.0)];
     let chicken_leg = vec![
         BodyPoint {
             dir: Vector2::new(0.2, -0.7),
             size: 0.1,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(-0.2, -0.7),
             size: 0.1,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(-0.3, 0.0),
             size: 0.1,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.1, 0.0),
             size: 0.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
     ];
-    Animal::new(vec![
+    let mut chicken = Animal::new(vec![
         BodyPoint {
             dir: Vector2::new(1.0, 0.0),
             size: 1.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
-            limbs: Vec::new(),
+            limbs: vec![Limb {
+                name: "comb".to_string(),
+                displacement: Vector3::new(0.2, 0.8, 0.0),
+                texture_displacement: 0,
+                body: vec![
+                    BodyPoint {
+                        dir: Vector2::new(0.1, 0.3),
+                        size: 0.2,
+                        texture_indices: texture_indices.clone(),
+                        discontinuous: false,
+                        limbs: Vec::new(),
+                    },
+                    BodyPoint {
+                        dir: Vector2::new(0.15, 0.25),
+                        size: 0.15,
+                        texture_indices: texture_indices.clone(),
+                        discontinuous: false,
+                        limbs: Vec::new(),
+                    },
+                    BodyPoint {
+                        dir: Vector2::new(0.1, 0.0),
+                        size: 0.0,
+                        texture_indices: texture_indices.clone(),
+                        discontinuous: false,
+                        limbs: Vec::new(),
+                    },
+                ],
+                role: None,
+                side: None,
+            }],
         },
         BodyPoint {
             dir: Vector2::new(0.5, -1.0),
             size: 0.5,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: true,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.75, -0.2),
             size: 1.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.75, 0.2),
             size: 1.1,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: vec![
                 Limb {
@@ -69,83 +99,97 @@ pub fn chicken() -> Animal {
                     displacement: Vector3::new(0.3, 0.1, -1.0),
                     texture_displacement: 5,
                     body: chicken_leg.clone(),
+                    role: None,
+                    side: None,
                 },
                 Limb {
                     name: "back_leg".to_string(),
                     displacement: Vector3::new(0.3, 0.1, 1.0),
                     texture_displacement: 5,
                     body: chicken_leg.clone(),
+                    role: None,
+                    side: None,
                 },
             ],
         },
         BodyPoint {
             dir: Vector2::new(0.75, 1.0),
             size: 1.0,
-            texture_indices: [Some((0, 1.0)), None],
+            texture_indices: vec![(0, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(1.0, 1.0),
             size: 0.1,
-            texture_indices: [Some((0, 1.0)), None],
+            texture_indices: vec![(0, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
-    ])
+    ]);
+    chicken.behavior = BehaviorParams {
+        walk_speed: 1.5,
+        run_speed: 5.0,
+        flee_distance: 6.0,
+        sight: 8.0,
+        sociability: 0.7,
+        hunger_rate: 0.05,
+        idle_time: 2.0,
+    };
+    chicken
 }
 
 pub fn turtle() -> Animal {
-    let texture_indices = [Some((2, 1.0)), None];
+    let texture_indices = vec![(2, 1.0)];
     let turtle_leg = vec![
         BodyPoint {
             dir: Vector2::new(0.65, -0.65),
             size: 0.3,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(-0.65, -0.65),
             size: 0.3,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.1, 0.0),
             size: 0.3,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
     ];
-    Animal::new(vec![
+    let mut turtle = Animal::new(vec![
         BodyPoint {
             dir: Vector2::new(1.0, 0.0),
             size: 1.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(2.0, 0.5),
             size: 10.0 / 16.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: true,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(2.0, 0.5) * 0.001,
             size: 1.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(2.0, 0.5),
             size: 2.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: vec![
                 Limb {
@@ -153,19 +197,23 @@ pub fn turtle() -> Animal {
                     displacement: Vector3::new(0.3, -0.2, -2.0),
                     texture_displacement: 5,
                     body: turtle_leg.clone(),
+                    role: None,
+                    side: None,
                 },
                 Limb {
                     name: "back_leg_0".to_string(),
                     displacement: Vector3::new(0.3, -0.2, -1.0),
                     texture_displacement: 5,
                     body: turtle_leg.clone(),
+                    role: None,
+                    side: None,
                 },
             ],
         },
         BodyPoint {
             dir: Vector2::new(2.0, -0.5),
             size: 3.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: vec![
                 Limb {
@@ -173,90 +221,104 @@ pub fn turtle() -> Animal {
                     displacement: Vector3::new(0.3, -0.5, -2.0),
                     texture_displacement: 5,
                     body: turtle_leg.clone(),
+                    role: None,
+                    side: None,
                 },
                 Limb {
                     name: "back_leg_1".to_string(),
                     displacement: Vector3::new(0.3, -0.5, -1.0),
                     texture_displacement: 5,
                     body: turtle_leg.clone(),
+                    role: None,
+                    side: None,
                 },
             ],
         },
         BodyPoint {
             dir: Vector2::new(2.0, -0.5).normalized() * 0.001,
             size: 2.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
-            limbs: Vec::new()
+            limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(1.0, -0.5),
             size: 0.5,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: true,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.001, 0.0),
             size: 0.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.001, 0.0),
             size: 0.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.001, 0.0),
             size: 0.0,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
-    ])
+    ]);
+    turtle.behavior = BehaviorParams {
+        walk_speed: 0.5,
+        run_speed: 1.0,
+        flee_distance: 2.0,
+        sight: 4.0,
+        sociability: 0.2,
+        hunger_rate: 0.01,
+        idle_time: 6.0,
+    };
+    turtle
 }
 
 pub fn fox() -> Animal {
-    let texture_indices = [Some((1, 1.0)), None];
+    let texture_indices = vec![(1, 1.0)];
     let fox_leg = vec![
         BodyPoint {
             dir: Vector2::new(0.45, -0.45),
             size: 0.2,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(-0.65, -0.65),
             size: 0.1,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.1, 0.0),
             size: 0.1,
-            texture_indices,
+            texture_indices: texture_indices.clone(),
             discontinuous: false,
             limbs: Vec::new(),
         },
     ];
-    Animal::new(vec![
+    let mut fox = Animal::new(vec![
         BodyPoint {
             dir: Vector2::new(1.0, 0.0),
             size: 1.0,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.75, 0.0),
             size: 0.5,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: true,
             limbs: vec![
                 Limb {
@@ -264,26 +326,30 @@ pub fn fox() -> Animal {
                     displacement: Vector3::new(0.3, 0.2, -2.0),
                     texture_displacement: 7,
                     body: fox_leg.clone(),
+                    role: None,
+                    side: None,
                 },
                 Limb {
                     name: "back_leg_2".to_string(),
                     displacement: Vector3::new(0.3, 0.2, -1.0),
                     texture_displacement: 7,
                     body: fox_leg.clone(),
+                    role: None,
+                    side: None,
                 },
             ],
         },
         BodyPoint {
             dir: Vector2::new(0.75, 0.0),
             size: 0.7,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(0.5, 0.0),
             size: 0.8,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: false,
             limbs: vec![
                 Limb {
@@ -291,42 +357,56 @@ pub fn fox() -> Animal {
                     displacement: Vector3::new(0.3, -0.2, -2.0),
                     texture_displacement: 7,
                     body: fox_leg.clone(),
+                    role: None,
+                    side: None,
                 },
                 Limb {
                     name: "back_leg_3".to_string(),
                     displacement: Vector3::new(0.3, -0.2, -1.0),
                     texture_displacement: 7,
                     body: fox_leg.clone(),
+                    role: None,
+                    side: None,
                 },
             ],
         },
         BodyPoint {
             dir: Vector2::new(0.5, 0.0),
             size: 0.7,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(1.0, 0.0),
             size: 0.1,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(1.0, 0.0),
             size: 1.0,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
         BodyPoint {
             dir: Vector2::new(1.0, 0.0),
             size: 0.1,
-            texture_indices: [Some((1, 1.0)), None],
+            texture_indices: vec![(1, 1.0)],
             discontinuous: false,
             limbs: Vec::new(),
         },
-    ])
+    ]);
+    fox.behavior = BehaviorParams {
+        walk_speed: 2.0,
+        run_speed: 7.0,
+        flee_distance: 4.0,
+        sight: 12.0,
+        sociability: 0.3,
+        hunger_rate: 0.03,
+        idle_time: 3.0,
+    };
+    fox
 }
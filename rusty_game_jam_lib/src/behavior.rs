@@ -0,0 +1,216 @@
+use gdnative::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// the herd has to be further away than this before an animal goes after it
+const HERD_DISTANCE: f32 = 3.0;
+/// how close an animal has to get to food to eat it
+const EAT_DISTANCE: f32 = 0.5;
+/// how much better another state has to score before an animal changes its mind
+const STICKINESS: f32 = 0.1;
+/// how much standing about or wandering scores, anything more pressing wins
+const ROAMING_SCORE: f32 = 0.3;
+/// what a threat right at the edge of the flee distance scores, clearly more than the 1 plus
+/// stickiness of a starving animal that's already after food
+const FLEE_SCORE: f32 = 1.0 + 2.0 * STICKINESS;
+
+/// how a species behaves, read from the creature file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToVariant, FromVariant)]
+#[serde(default)]
+pub struct BehaviorParams {
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    /// how close a threat has to get before the animal runs away
+    pub flee_distance: f32,
+    /// how far away food and the rest of the herd can be seen
+    pub sight: f32,
+    /// how much the animal wants to stay with the herd, from 0 to 1
+    pub sociability: f32,
+    /// how much hungrier the animal gets every second, it's starving at 1
+    pub hunger_rate: f32,
+    /// roughly how long the animal stands about or wanders in one direction for
+    pub idle_time: f32,
+}
+
+impl Default for BehaviorParams {
+    fn default() -> Self {
+        Self {
+            walk_speed: 1.5,
+            run_speed: 4.0,
+            flee_distance: 4.0,
+            sight: 8.0,
+            sociability: 0.5,
+            hunger_rate: 0.02,
+            idle_time: 3.0,
+        }
+    }
+}
+
+impl BehaviorParams {
+    /// the weighted average of some parameters, for blended animals
+    pub fn blend(params: &[(&BehaviorParams, f32)]) -> Self {
+        let weight_sum = params.iter().map(|x| x.1).sum::<f32>();
+        if params.is_empty() || weight_sum <= 0.0 {
+            return params.first().map(|x| x.0.clone()).unwrap_or_default();
+        }
+        let average = |field: fn(&BehaviorParams) -> f32| {
+            params.iter().map(|(x, w)| field(x) * w).sum::<f32>() / weight_sum
+        };
+        Self {
+            walk_speed: average(|x| x.walk_speed),
+            run_speed: average(|x| x.run_speed),
+            flee_distance: average(|x| x.flee_distance),
+            sight: average(|x| x.sight),
+            sociability: average(|x| x.sociability),
+            hunger_rate: average(|x| x.hunger_rate),
+            idle_time: average(|x| x.idle_time),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BehaviorState {
+    Idle,
+    Wander,
+    Flee,
+    SeekFood,
+    FollowHerd,
+}
+
+/// what an animal can see this tick, as positions along the x axis
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Senses {
+    pub position: f32,
+    pub threats: Vec<f32>,
+    pub food: Vec<f32>,
+    /// every other animal
+    pub herd: Vec<f32>,
+}
+
+impl Senses {
+    /// the offset to the closest of `things` no further away than `range`
+    fn nearest(&self, things: &[f32], range: f32) -> Option<f32> {
+        things
+            .iter()
+            .map(|x| x - self.position)
+            .filter(|x| x.abs() <= range)
+            .min_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap_or(Ordering::Less))
+    }
+
+    /// the offset to the middle of the part of the herd no further away than `range`
+    fn herd_center(&self, range: f32) -> Option<f32> {
+        let nearby = self
+            .herd
+            .iter()
+            .map(|x| x - self.position)
+            .filter(|x| x.abs() <= range)
+            .collect::<Vec<_>>();
+        if nearby.is_empty() {
+            None
+        } else {
+            Some(nearby.iter().sum::<f32>() / nearby.len() as f32)
+        }
+    }
+}
+
+/// decides what an animal wants to do by scoring every state and picking the best one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Behavior {
+    pub params: BehaviorParams,
+    pub state: BehaviorState,
+    /// 0 is full, 1 is starving
+    pub hunger: f32,
+    /// idle or wander, whichever the animal does when nothing more pressing is going on
+    roaming: BehaviorState,
+    /// seconds until the animal reconsiders whether to idle or wander
+    timer: f32,
+    /// which way the animal wanders, -1 or 1
+    heading: f32,
+}
+
+impl Behavior {
+    pub fn new(params: BehaviorParams) -> Self {
+        Self {
+            params,
+            state: BehaviorState::Idle,
+            hunger: 0.0,
+            roaming: BehaviorState::Idle,
+            timer: 0.0,
+            heading: 1.0,
+        }
+    }
+
+    fn score(&self, state: BehaviorState, senses: &Senses) -> f32 {
+        let params = &self.params;
+        let score = match state {
+            BehaviorState::Idle | BehaviorState::Wander if state == self.roaming => ROAMING_SCORE,
+            BehaviorState::Idle | BehaviorState::Wander => 0.0,
+            // running away beats everything else, even with the threat at the edge of the flee
+            // distance, and the closer the threat the higher the score
+            BehaviorState::Flee => senses
+                .nearest(&senses.threats, params.flee_distance)
+                .map_or(0.0, |x| {
+                    FLEE_SCORE + 1.0 - x.abs() / params.flee_distance.max(f32::EPSILON)
+                }),
+            BehaviorState::SeekFood => senses
+                .nearest(&senses.food, params.sight)
+                .map_or(0.0, |_| self.hunger),
+            BehaviorState::FollowHerd => senses.herd_center(params.sight).map_or(0.0, |x| {
+                params.sociability * ((x.abs() - HERD_DISTANCE) / HERD_DISTANCE).clamp(0.0, 1.0)
+            }),
+        };
+        if state == self.state {
+            score + STICKINESS
+        } else {
+            score
+        }
+    }
+
+    /// update the state for this tick, returning how fast the animal wants to move along x
+    pub fn tick(&mut self, delta: f32, senses: &Senses, rng: &mut impl Rng) -> f32 {
+        self.hunger = (self.hunger + self.params.hunger_rate * delta).min(1.0);
+        self.timer -= delta;
+        if self.timer <= 0.0 {
+            self.roaming = if rng.gen::<bool>() {
+                BehaviorState::Idle
+            } else {
+                BehaviorState::Wander
+            };
+            self.heading = if rng.gen::<bool>() { 1.0 } else { -1.0 };
+            self.timer = self.params.idle_time * rng.gen_range(0.5..1.5);
+        }
+
+        self.state = [
+            BehaviorState::Idle,
+            BehaviorState::Wander,
+            BehaviorState::Flee,
+            BehaviorState::SeekFood,
+            BehaviorState::FollowHerd,
+        ]
+        .into_iter()
+        .map(|state| (state, self.score(state, senses)))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Less))
+        .map_or(BehaviorState::Idle, |x| x.0);
+
+        let params = &self.params;
+        match self.state {
+            BehaviorState::Idle => 0.0,
+            BehaviorState::Wander => self.heading * params.walk_speed,
+            BehaviorState::Flee => senses
+                .nearest(&senses.threats, params.flee_distance)
+                .map_or(0.0, |x| -x.signum() * params.run_speed),
+            BehaviorState::SeekFood => match senses.nearest(&senses.food, params.sight) {
+                Some(x) if x.abs() <= EAT_DISTANCE => {
+                    self.hunger = 0.0;
+                    0.0
+                }
+                Some(x) => x.signum() * params.walk_speed,
+                None => 0.0,
+            },
+            BehaviorState::FollowHerd => senses
+                .herd_center(params.sight)
+                .map_or(0.0, |x| x.signum() * params.walk_speed),
+        }
+    }
+}
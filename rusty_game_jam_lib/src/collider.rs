@@ -0,0 +1,136 @@
+use crate::animal::{Animal, BodyPoint};
+use gdnative::prelude::*;
+use itertools::Itertools;
+use std::cmp::Ordering;
+
+/// animals are flat, but reach this far in front of and behind their middle for colliding
+pub const HALF_DEPTH: f32 = 1.0;
+/// capsules are never thinner than this, so pointy tips still collide
+const MIN_RADIUS: f32 = 0.05;
+
+/// the shape an animal collides with the world as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColliderShape {
+    /// one box around the whole animal
+    Box,
+    /// a capsule around every segment of the spine
+    Capsules,
+    /// a capsule around every segment of every limb
+    CapsulesAndLegs,
+    /// the convex hull of the animal's meshes
+    ConvexHull,
+}
+
+impl ColliderShape {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(Self::Box),
+            "capsules" => Some(Self::Capsules),
+            "capsules_and_legs" => Some(Self::CapsulesAndLegs),
+            "hull" => Some(Self::ConvexHull),
+            _ => None,
+        }
+    }
+}
+
+/// a capsule around one segment of a limb, relative to the animal's skeleton
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub from: Vector3,
+    pub to: Vector3,
+    pub radius: f32,
+}
+
+/// the height godot wants for a capsule reaching `length` from end to end, which only counts the
+/// straight part between the two round caps
+pub fn capsule_height(length: f32, radius: f32) -> f32 {
+    (length - 2.0 * radius).max(0.0)
+}
+
+impl Capsule {
+    pub fn height(&self) -> f32 {
+        capsule_height(self.from.distance_to(self.to), self.radius)
+    }
+}
+
+/// how thick a capsule around the segment between two points of a limb is
+pub fn segment_radius(first: &BodyPoint, second: &BodyPoint) -> f32 {
+    (first.size.max(second.size) * 0.5).max(MIN_RADIUS)
+}
+
+/// capsules around every segment of the spine, and of every other limb too if `limbs` is set.
+/// limbs are placed the same way their meshes are
+pub fn segment_capsules(animal: &Animal, limbs: bool) -> Vec<Capsule> {
+    let mut capsules = Vec::new();
+    let mut stack = vec![(&animal.body, Vector2::ZERO)];
+    while let Some((limb, root)) = stack.pop() {
+        let z = limb.displacement.z;
+        let mut posn = root + Vector2::new(limb.displacement.x, limb.displacement.y);
+        for (first, second) in limb.body.iter().tuple_windows() {
+            capsules.push(Capsule {
+                from: Vector3::new(posn.x, posn.y, z),
+                to: Vector3::new(posn.x + first.dir.x, posn.y + first.dir.y, z),
+                radius: segment_radius(first, second),
+            });
+            posn += first.dir;
+        }
+
+        if limbs {
+            let mut posn = root;
+            for point in &limb.body {
+                stack.extend(point.limbs.iter().map(|x| (x, posn)));
+                posn += point.dir;
+            }
+        }
+    }
+    capsules
+}
+
+/// the corners of the smallest convex polygon around `points`, anticlockwise
+pub fn convex_hull(points: &[Vector2]) -> Vec<Vector2> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| {
+        (a.x, a.y)
+            .partial_cmp(&(b.x, b.y))
+            .unwrap_or(Ordering::Less)
+    });
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    // whether going from `a` to `b` to `c` turns anticlockwise
+    let turns_left = |a: Vector2, b: Vector2, c: Vector2| {
+        let (ab, ac) = (b - a, c - a);
+        ab.x * ac.y - ab.y * ac.x > 0.0
+    };
+    let half_hull = |points: &mut dyn Iterator<Item = &Vector2>| {
+        let mut hull: Vec<Vector2> = Vec::new();
+        for point in points {
+            while hull.len() >= 2 && !turns_left(hull[hull.len() - 2], hull[hull.len() - 1], *point)
+            {
+                hull.pop();
+            }
+            hull.push(*point);
+        }
+        // the last point is where the other half starts
+        hull.pop();
+        hull
+    };
+    let mut hull = half_hull(&mut points.iter());
+    hull.extend(half_hull(&mut points.iter().rev()));
+    hull
+}
+
+/// points for a convex shape around the outline of an animal's meshes, as deep as the animal is
+pub fn extruded_hull(vertices: &[Vector2]) -> Vec<Vector3> {
+    convex_hull(vertices)
+        .into_iter()
+        .flat_map(|x| {
+            [
+                Vector3::new(x.x, x.y, -HALF_DEPTH),
+                Vector3::new(x.x, x.y, HALF_DEPTH),
+            ]
+        })
+        .collect()
+}
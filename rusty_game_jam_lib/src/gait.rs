@@ -0,0 +1,169 @@
+use crate::animal::LimbSide;
+use std::cmp::Ordering;
+
+/// below this speed the legs stop stepping
+const MIN_SPEED: f32 = 0.05;
+/// legs attached closer together than this along the body count as one pair
+const PAIR_DISTANCE: f32 = 0.01;
+
+/// how the legs of an animal are coordinated with each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gait {
+    /// one foot at a time, left and right half a cycle apart
+    Walk,
+    /// diagonal legs move together
+    Trot,
+    /// front legs together, then back legs together
+    Gallop,
+    /// every leg at once
+    Hop,
+}
+
+impl Gait {
+    /// the natural gait for moving at `speed` with legs `limb_length` long, `pairs` is how many
+    /// pairs of legs there are
+    pub fn for_speed(speed: f32, limb_length: f32, pairs: usize) -> Self {
+        let relative_speed = speed.abs() / limb_length.max(f32::EPSILON);
+        if pairs <= 1 {
+            if relative_speed < 3.0 {
+                Self::Walk
+            } else {
+                Self::Hop
+            }
+        } else if relative_speed < 1.5 {
+            Self::Walk
+        } else if relative_speed < 4.0 {
+            Self::Trot
+        } else {
+            Self::Gallop
+        }
+    }
+
+    /// the fraction of a cycle each foot spends on the ground
+    pub fn duty_factor(self) -> f32 {
+        match self {
+            Self::Walk => 0.6,
+            Self::Trot => 0.5,
+            Self::Gallop => 0.4,
+            Self::Hop => 0.5,
+        }
+    }
+
+    /// how far the body moves in one cycle, relative to leg length
+    pub fn stride_scale(self) -> f32 {
+        match self {
+            Self::Walk => 1.0,
+            Self::Trot => 1.4,
+            Self::Gallop => 2.0,
+            Self::Hop => 1.6,
+        }
+    }
+
+    /// where in the cycle (0 to 1) a leg lifts off. `pair` counts from the head
+    pub fn phase(self, side: LimbSide, pair: usize, pairs: usize) -> f32 {
+        let side = match side {
+            LimbSide::Front | LimbSide::Center => 0.0,
+            LimbSide::Back => 1.0,
+        };
+        // 1 at the front of the body down to 0 at the back
+        let along = if pairs > 1 {
+            1.0 - pair as f32 / (pairs - 1) as f32
+        } else {
+            0.0
+        };
+        let phase = match self {
+            Self::Walk => side * 0.5 + along * 0.25,
+            Self::Trot => side * 0.5 + pair as f32 * 0.5,
+            Self::Gallop => side * 0.1 + along * 0.5,
+            Self::Hop => 0.0,
+        };
+        phase.rem_euclid(1.0)
+    }
+}
+
+/// a leg should lift off now and land `reach` in front of where it's attached, `duration` seconds
+/// from now
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub leg: usize,
+    pub reach: f32,
+    pub duration: f32,
+}
+
+/// keeps track of the step cycle and decides when each leg steps
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaitPlanner {
+    pub gait: Gait,
+    limb_length: f32,
+    phase: f32,
+    /// side and pair of every leg
+    legs: Vec<(LimbSide, usize)>,
+    pairs: usize,
+}
+
+impl GaitPlanner {
+    /// `legs` are the side of each leg and how far down the spine from the head it's attached,
+    /// pairs are counted from the head
+    pub fn new(legs: &[(LimbSide, f32)], limb_length: f32) -> Self {
+        let mut attachments = legs.iter().map(|x| x.1).collect::<Vec<_>>();
+        attachments.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Less));
+        attachments.dedup_by(|a, b| (*a - *b).abs() < PAIR_DISTANCE);
+        let legs = legs
+            .iter()
+            .map(|(side, x)| {
+                let pair = attachments
+                    .iter()
+                    .position(|y| (x - y).abs() < PAIR_DISTANCE)
+                    .unwrap_or(0);
+                (*side, pair)
+            })
+            .collect();
+        let pairs = attachments.len();
+        Self {
+            gait: Gait::for_speed(0.0, limb_length, pairs),
+            limb_length,
+            phase: 0.0,
+            legs,
+            pairs,
+        }
+    }
+
+    /// how far the body moves during one cycle of the current gait
+    pub fn stride(&self) -> f32 {
+        self.limb_length * self.gait.stride_scale()
+    }
+
+    /// move the cycle along by `delta` seconds with the body moving at `velocity`, returning the
+    /// legs that have to start stepping
+    pub fn tick(&mut self, delta: f32, velocity: f32) -> Vec<Step> {
+        let speed = velocity.abs();
+        if speed < MIN_SPEED || self.limb_length <= 0.0 {
+            return Vec::new();
+        }
+        self.gait = Gait::for_speed(speed, self.limb_length, self.pairs);
+        let stride = self.stride();
+        let duty_factor = self.gait.duty_factor();
+        let period = stride / speed;
+
+        let last_phase = self.phase;
+        let phase = last_phase + delta / period;
+        let steps = self
+            .legs
+            .iter()
+            .enumerate()
+            .filter(|(_, (side, pair))| {
+                let offset = self.gait.phase(*side, *pair, self.pairs);
+                (phase - offset).floor() > (last_phase - offset).floor()
+            })
+            .map(|(leg, _)| Step {
+                leg,
+                // the body keeps moving while the foot is in the air, and the foot should land
+                // halfway through the distance it'll be on the ground for
+                reach: stride * (1.0 - duty_factor * 0.5) * velocity.signum(),
+                duration: period * (1.0 - duty_factor),
+            })
+            .collect();
+        self.phase = phase.rem_euclid(1.0);
+        steps
+    }
+}
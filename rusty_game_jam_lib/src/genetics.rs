@@ -0,0 +1,201 @@
+use crate::animal::{blend_animals, limb_role, Animal, AnimalError, BodyGradient, Limb};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+
+/// how likely each mutation is, and how strong it is when it happens
+#[derive(Debug, Clone, PartialEq)]
+pub struct MutationRates {
+    /// chance per body point of rotating and stretching its direction
+    pub dir: f32,
+    /// max rotation in radians, and max relative change in length
+    pub dir_amount: f32,
+    /// chance per body point of changing its size
+    pub size: f32,
+    /// max relative change in size
+    pub size_amount: f32,
+    /// chance per animal of gaining or losing a set of limbs
+    pub limb_count: f32,
+    /// chance per limb of swapping one of its textures for another row of the atlas
+    pub texture: f32,
+    /// number of texture rows in the atlas
+    pub texture_count: usize,
+}
+
+impl MutationRates {
+    /// the usual rates, for an atlas with `texture_count` rows
+    pub fn new(texture_count: usize) -> Self {
+        Self {
+            dir: 0.1,
+            dir_amount: 0.2,
+            size: 0.1,
+            size_amount: 0.2,
+            limb_count: 0.05,
+            texture: 0.02,
+            texture_count,
+        }
+    }
+}
+
+/// a pair of complementary gradients that wander smoothly between the two parents along the body
+pub fn random_gradients(len: usize, rng: &mut impl Rng) -> (BodyGradient, BodyGradient) {
+    let mut first = Vec::new();
+    let mut weight = rng.gen::<f32>();
+    for _ in 0..len {
+        first.push(weight);
+        weight = (weight + rng.gen_range(-0.3..0.3)).clamp(0.0, 1.0);
+    }
+    let second = first.iter().map(|x| 1.0 - x).collect();
+    (BodyGradient(first), BodyGradient(second))
+}
+
+/// breed two animals by blending them with random gradients, also returns the gradients
+pub fn crossover(
+    a: &Animal,
+    b: &Animal,
+    rng: &mut impl Rng,
+) -> Result<(Animal, [BodyGradient; 2]), AnimalError> {
+    let (a_len, b_len) = (a.body.body.len(), b.body.body.len());
+    let (mut a_gradient, mut b_gradient) = random_gradients(a_len.max(b_len), rng);
+    // past the end of the shorter parent, the longer one is all there is
+    for i in a_len.min(b_len)..a_len.max(b_len) {
+        a_gradient.0[i] = if i < a_len { 1.0 } else { 0.0 };
+        b_gradient.0[i] = if i < b_len { 1.0 } else { 0.0 };
+    }
+    let parents = [(a.clone(), a_gradient), (b.clone(), b_gradient)];
+    let child = blend_animals(&parents)?;
+    let [(_, a_gradient), (_, b_gradient)] = parents;
+    Ok((child, [a_gradient, b_gradient]))
+}
+
+fn mutate_dir(limb: &mut Limb, chance: f32, amount: f32, rng: &mut impl Rng) {
+    for point in &mut limb.body {
+        if rng.gen::<f32>() < chance {
+            let angle = rng.gen_range(-amount..=amount);
+            let stretch = 1.0 + rng.gen_range(-amount..=amount);
+            point.dir = point.dir.rotated(angle) * stretch;
+        }
+    }
+}
+
+fn mutate_size(limb: &mut Limb, chance: f32, amount: f32, rng: &mut impl Rng) {
+    for point in &mut limb.body {
+        if rng.gen::<f32>() < chance {
+            point.size = (point.size * (1.0 + rng.gen_range(-amount..=amount))).max(0.0);
+        }
+    }
+}
+
+/// swap one of the textures used by a limb for one it doesn't use yet, keeping neighbouring
+/// points compatible with each other
+fn mutate_texture(limb: &mut Limb, texture_count: usize, rng: &mut impl Rng) {
+    let used: HashSet<usize> = limb
+        .body
+        .iter()
+        .flat_map(|point| point.texture_indices.iter().map(|x| x.0))
+        .collect();
+    let unused: Vec<usize> = (0..texture_count).filter(|x| !used.contains(x)).collect();
+    if used.is_empty() || unused.is_empty() {
+        return;
+    }
+    let mut used: Vec<usize> = used.into_iter().collect();
+    used.sort_unstable();
+    let old = used[rng.gen_range(0..used.len())];
+    let new = unused[rng.gen_range(0..unused.len())];
+    for point in &mut limb.body {
+        for (texture, _) in &mut point.texture_indices {
+            if *texture == old {
+                *texture = new;
+            }
+        }
+    }
+}
+
+fn limb_names(limb: &Limb, names: &mut HashSet<String>) {
+    names.insert(limb.name.clone());
+    for point in &limb.body {
+        for child in &point.limbs {
+            limb_names(child, names);
+        }
+    }
+}
+
+/// give a copied limb (and everything attached to it) names that aren't taken yet
+fn rename_limb(limb: &mut Limb, names: &mut HashSet<String>) {
+    let role = limb_role(&limb.name).to_string();
+    let mut n = 0;
+    while names.contains(&format!("{}_{}", role, n)) {
+        n += 1;
+    }
+    limb.name = format!("{}_{}", role, n);
+    names.insert(limb.name.clone());
+    for point in &mut limb.body {
+        for child in &mut point.limbs {
+            rename_limb(child, names);
+        }
+    }
+}
+
+/// either copy the limbs of one spine point onto another point that has none, or drop the limbs
+/// of a spine point
+fn mutate_limb_count(animal: &mut Animal, rng: &mut impl Rng) {
+    let spine = &mut animal.body.body;
+    let with_limbs: Vec<usize> = (0..spine.len())
+        .filter(|i| !spine[*i].limbs.is_empty())
+        .collect();
+    let without_limbs: Vec<usize> = (0..spine.len())
+        .filter(|i| spine[*i].limbs.is_empty())
+        .collect();
+    if with_limbs.is_empty() {
+        return;
+    }
+    let source = with_limbs[rng.gen_range(0..with_limbs.len())];
+    if without_limbs.is_empty() || rng.gen::<bool>() {
+        spine[source].limbs.clear();
+    } else {
+        let mut names = HashSet::new();
+        limb_names(&animal.body, &mut names);
+        let spine = &mut animal.body.body;
+        let target = without_limbs[rng.gen_range(0..without_limbs.len())];
+        let mut limbs = spine[source].limbs.clone();
+        for limb in &mut limbs {
+            rename_limb(limb, &mut names);
+        }
+        spine[target].limbs = limbs;
+    }
+}
+
+fn mutate_limb(limb: &mut Limb, rates: &MutationRates, rng: &mut impl Rng) {
+    mutate_dir(limb, rates.dir, rates.dir_amount, rng);
+    mutate_size(limb, rates.size, rates.size_amount, rng);
+    if rng.gen::<f32>() < rates.texture {
+        mutate_texture(limb, rates.texture_count, rng);
+    }
+    for point in &mut limb.body {
+        for child in &mut point.limbs {
+            mutate_limb(child, rates, rng);
+        }
+    }
+}
+
+/// randomly change an animal in place
+pub fn mutate(animal: &mut Animal, rates: &MutationRates, rng: &mut impl Rng) {
+    if rng.gen::<f32>() < rates.limb_count {
+        mutate_limb_count(animal, rng);
+    }
+    mutate_limb(&mut animal.body, rates, rng);
+}
+
+/// crossover followed by mutation, the same seed always breeds the same child. also returns how
+/// much of each parent went into every point of the child's spine
+pub fn breed(
+    a: &Animal,
+    b: &Animal,
+    rates: &MutationRates,
+    seed: u64,
+) -> Result<(Animal, [BodyGradient; 2]), AnimalError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut child, gradients) = crossover(a, b, &mut rng)?;
+    mutate(&mut child, rates, &mut rng);
+    Ok((child, gradients))
+}
@@ -0,0 +1,51 @@
+use gdnative::prelude::*;
+
+/// the furthest the body leans to follow the ground, in radians
+const MAX_TILT: f32 = 0.5;
+
+/// something feet can stand on
+pub trait Ground {
+    /// the first point on the ground straight down from `from`, no more than `depth` below it
+    fn raycast_down(&self, from: Vector2, depth: f32) -> Option<Vector2>;
+}
+
+/// where a foot stepping `reach` in front of the leg's `root` should land. the ground is searched
+/// for from a little above the root so feet can step up onto things, and when there's nothing to
+/// stand on the foot hangs `drop` below the root
+pub fn place_foot(
+    ground: &dyn Ground,
+    root: Vector2,
+    reach: f32,
+    limb_length: f32,
+    drop: f32,
+) -> Vector2 {
+    let from = Vector2::new(root.x + reach, root.y + limb_length * 0.5);
+    ground
+        .raycast_down(from, limb_length * 2.0)
+        .unwrap_or_else(|| Vector2::new(root.x + reach, root.y - drop))
+}
+
+/// the angle of the line that best fits the feet, so the body can lean with the ground
+pub fn body_tilt(feet: &[Vector2]) -> f32 {
+    if feet.is_empty() {
+        return 0.0;
+    }
+    let mean = feet.iter().fold(Vector2::ZERO, |acc, x| acc + *x) / feet.len() as f32;
+    let (covariance, variance) = feet.iter().fold((0.0, 0.0), |(covariance, variance), x| {
+        let diff = *x - mean;
+        (covariance + diff.x * diff.y, variance + diff.x * diff.x)
+    });
+    if variance <= f32::EPSILON {
+        return 0.0;
+    }
+    (covariance / variance).atan().clamp(-MAX_TILT, MAX_TILT)
+}
+
+/// the average height of the feet, or `None` if there aren't any
+pub fn average_height(feet: &[Vector2]) -> Option<f32> {
+    if feet.is_empty() {
+        None
+    } else {
+        Some(feet.iter().map(|x| x.y).sum::<f32>() / feet.len() as f32)
+    }
+}
@@ -0,0 +1,264 @@
+use crate::animal::LimbRole;
+use gdnative::prelude::*;
+use std::f32::consts::PI;
+
+/// how far a single joint can bend either way when a limb has nothing else to go on
+const MAX_BEND: f32 = 2.5;
+/// how far each joint of a tail or neck can bend either way
+const MAX_WAG: f32 = 0.6;
+
+/// moves a chain of joints so its tip reaches for a target. `joints[0]` is the root and never
+/// moves, and the distances between neighbouring joints are kept as they are
+pub trait IkSolver {
+    /// returns whether the tip ended up within reach of the target
+    fn solve(&self, joints: &mut [Vector2], target: Vector2) -> bool;
+}
+
+/// how far a joint can bend, in radians, measured from the direction of the segment before it.
+/// positive is anticlockwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointLimit {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl JointLimit {
+    pub const FREE: Self = Self { min: -PI, max: PI };
+
+    pub fn symmetric(max: f32) -> Self {
+        Self { min: -max, max }
+    }
+
+    pub fn clamp(&self, angle: f32) -> f32 {
+        angle.clamp(self.min, self.max)
+    }
+
+    /// one limit per inner joint, letting each bend up to `max` the way it's already bent at rest
+    /// but not the other way, so knees keep pointing the way they were built
+    pub fn from_rest(joints: &[Vector2], max: f32) -> Vec<Self> {
+        segments(joints)
+            .windows(2)
+            .map(|x| {
+                let bend = bend_angle(x[0], x[1]);
+                if bend > f32::EPSILON {
+                    Self {
+                        min: 0.0,
+                        max: max.max(bend),
+                    }
+                } else if bend < -f32::EPSILON {
+                    Self {
+                        min: -max.max(-bend),
+                        max: 0.0,
+                    }
+                } else {
+                    Self::symmetric(max)
+                }
+            })
+            .collect()
+    }
+}
+
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// anticlockwise angle from the x axis, to match `Vector2::rotated` (`Vector2::angle` goes clockwise)
+fn angle(v: Vector2) -> f32 {
+    v.y.atan2(v.x)
+}
+
+/// the angle needed to turn `from` to face the same way as `to`
+pub fn bend_angle(from: Vector2, to: Vector2) -> f32 {
+    wrap_angle(angle(to) - angle(from))
+}
+
+fn segments(joints: &[Vector2]) -> Vec<Vector2> {
+    joints.windows(2).map(|x| x[1] - x[0]).collect()
+}
+
+fn direction(v: Vector2, fallback: Vector2) -> Vector2 {
+    if v.length_squared() > f32::EPSILON {
+        v.normalized()
+    } else {
+        fallback
+    }
+}
+
+fn tip_reached(joints: &[Vector2], target: Vector2, tolerance: f32) -> bool {
+    matches!(joints.last(), Some(tip) if tip.distance_to(target) <= tolerance)
+}
+
+/// forward and backward reaching, with the forward pass bending each joint back inside its limits
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fabrik {
+    pub iterations: usize,
+    pub tolerance: f32,
+    /// `limits[i]` is for the joint between segments `i` and `i + 1`, missing ones are free
+    pub limits: Vec<JointLimit>,
+}
+
+impl IkSolver for Fabrik {
+    fn solve(&self, joints: &mut [Vector2], target: Vector2) -> bool {
+        if joints.len() < 2 {
+            return false;
+        }
+        let last = joints.len() - 1;
+        let lengths = segments(joints)
+            .iter()
+            .map(|x| x.length())
+            .collect::<Vec<_>>();
+        let root = joints[0];
+        for _ in 0..self.iterations {
+            if tip_reached(joints, target, self.tolerance) {
+                return true;
+            }
+
+            joints[last] = target;
+            for i in (0..last).rev() {
+                let dir = direction(joints[i] - joints[i + 1], Vector2::new(0.0, 1.0));
+                joints[i] = joints[i + 1] + dir * lengths[i];
+            }
+
+            joints[0] = root;
+            let mut last_dir = None;
+            for i in 1..=last {
+                let mut dir = direction(joints[i] - joints[i - 1], Vector2::new(0.0, -1.0));
+                if let Some(last_dir) = last_dir {
+                    let limit = self.limits.get(i - 2).unwrap_or(&JointLimit::FREE);
+                    let bend = bend_angle(last_dir, dir);
+                    dir = dir.rotated(limit.clamp(bend) - bend);
+                }
+                joints[i] = joints[i - 1] + dir * lengths[i - 1];
+                last_dir = Some(dir);
+            }
+        }
+        tip_reached(joints, target, self.tolerance)
+    }
+}
+
+/// cyclic coordinate descent, turns one joint at a time from the tip back to the root
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ccd {
+    pub iterations: usize,
+    pub tolerance: f32,
+    /// `limits[i]` is for the joint between segments `i` and `i + 1`, missing ones are free
+    pub limits: Vec<JointLimit>,
+}
+
+impl IkSolver for Ccd {
+    fn solve(&self, joints: &mut [Vector2], target: Vector2) -> bool {
+        if joints.len() < 2 {
+            return false;
+        }
+        let last = joints.len() - 1;
+        for _ in 0..self.iterations {
+            if tip_reached(joints, target, self.tolerance) {
+                return true;
+            }
+            for i in (0..last).rev() {
+                let pivot = joints[i];
+                let (to_tip, to_target) = (joints[last] - pivot, target - pivot);
+                if to_tip.length_squared() <= f32::EPSILON
+                    || to_target.length_squared() <= f32::EPSILON
+                {
+                    continue;
+                }
+                let mut rotation = bend_angle(to_tip, to_target);
+                if i > 0 {
+                    let limit = self.limits.get(i - 1).unwrap_or(&JointLimit::FREE);
+                    let bend = bend_angle(joints[i] - joints[i - 1], joints[i + 1] - pivot);
+                    rotation = limit.clamp(bend + rotation) - bend;
+                }
+                for joint in &mut joints[i + 1..] {
+                    *joint = pivot + (*joint - pivot).rotated(rotation);
+                }
+            }
+        }
+        tip_reached(joints, target, self.tolerance)
+    }
+}
+
+/// exact solution for a chain of two segments, bending the middle joint the same way it bends at
+/// rest. leaves longer or shorter chains alone
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoBone {
+    pub tolerance: f32,
+    /// which way the middle joint bends, positive is anticlockwise
+    pub bend: f32,
+}
+
+impl TwoBone {
+    pub fn from_rest(joints: &[Vector2], tolerance: f32) -> Self {
+        let segments = segments(joints);
+        let bend = match segments.as_slice() {
+            [first, second] => bend_angle(*first, *second),
+            _ => 0.0,
+        };
+        Self { tolerance, bend }
+    }
+}
+
+impl IkSolver for TwoBone {
+    fn solve(&self, joints: &mut [Vector2], target: Vector2) -> bool {
+        if joints.len() != 3 {
+            return false;
+        }
+        let (first, second) = (
+            joints[0].distance_to(joints[1]),
+            joints[1].distance_to(joints[2]),
+        );
+        let root = joints[0];
+        let to_target = direction(
+            target - root,
+            direction(joints[2] - root, Vector2::new(0.0, -1.0)),
+        );
+        let distance = root
+            .distance_to(target)
+            .clamp((first - second).abs(), first + second);
+
+        // law of cosines for the angle between the first segment and the line to the target
+        let cos = if first * distance > f32::EPSILON {
+            (first * first + distance * distance - second * second) / (2.0 * first * distance)
+        } else {
+            1.0
+        };
+        let angle = cos.clamp(-1.0, 1.0).acos();
+        // turning the first segment one way makes the second bend back the other way
+        let angle = if self.bend < 0.0 { angle } else { -angle };
+
+        let reach = root + to_target * distance;
+        joints[1] = root + to_target.rotated(angle) * first;
+        joints[2] = joints[1] + direction(reach - joints[1], to_target) * second;
+        tip_reached(joints, target, self.tolerance)
+    }
+}
+
+/// how far each inner joint of a limb can bend, `rest` is the limb's chain of joints at rest
+pub fn limits_for(role: LimbRole, rest: &[Vector2]) -> Vec<JointLimit> {
+    match role {
+        LimbRole::Leg | LimbRole::Arm => JointLimit::from_rest(rest, MAX_BEND),
+        LimbRole::Spine | LimbRole::Tail | LimbRole::Neck | LimbRole::Appendage => {
+            vec![JointLimit::symmetric(MAX_WAG); rest.len().saturating_sub(2)]
+        }
+    }
+}
+
+/// the solver a limb gets by default, `rest` is the limb's chain of joints at rest
+pub fn solver_for(role: LimbRole, rest: &[Vector2]) -> Box<dyn IkSolver> {
+    let tolerance = 0.1;
+    match role {
+        LimbRole::Leg | LimbRole::Arm if rest.len() == 3 => {
+            Box::new(TwoBone::from_rest(rest, tolerance))
+        }
+        LimbRole::Leg | LimbRole::Arm => Box::new(Fabrik {
+            iterations: 10,
+            tolerance,
+            limits: limits_for(role, rest),
+        }),
+        LimbRole::Spine | LimbRole::Tail | LimbRole::Neck | LimbRole::Appendage => Box::new(Ccd {
+            iterations: 10,
+            tolerance,
+            limits: limits_for(role, rest),
+        }),
+    }
+}
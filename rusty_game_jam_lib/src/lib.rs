@@ -1,9 +1,22 @@
 mod animal;
+mod animal_loader;
 mod animal_spawner;
 mod animal_controller;
+#[cfg(test)]
 mod animal_templates;
+mod behavior;
+mod collider;
+mod limb_mesh;
 mod free_cam;
+mod gait;
+mod genetics;
+mod ground;
+mod ik;
+mod look;
 mod prop_ref;
+mod ragdoll;
+mod secondary;
+mod target;
 
 
 use animal_controller::AnimalController;
@@ -55,25 +68,76 @@ godot_init!(init);
 
 #[cfg(test)]
 mod tests {
-    use crate::animal::{blend_animals, Animal, BodyGradient, BodyPoint};
+    use crate::animal::{
+        blend_animals, limb_infos, Animal, AnimalError, BodyGradient, BodyPoint, GradientPreset,
+        Limb, LimbRole, LimbSide,
+    };
+    use crate::animal_loader::{load_animal, load_animals, save_animal};
+    use crate::animal_templates;
+    use crate::behavior::{Behavior, BehaviorParams, BehaviorState, Senses};
+    use crate::collider::{
+        capsule_height, convex_hull, extruded_hull, segment_capsules, ColliderShape, HALF_DEPTH,
+    };
+    use crate::gait::{Gait, GaitPlanner};
+    use crate::genetics::{breed, crossover, mutate, MutationRates};
+    use crate::ground::{average_height, body_tilt, place_foot, Ground};
+    use crate::ik::{bend_angle, Ccd, Fabrik, IkSolver, JointLimit, TwoBone};
+    use crate::limb_mesh::{
+        build_limb_mesh, match_texture_indices, LimbMeshData, LimbShape, MeshStyle, Outline,
+    };
+    use crate::look::{look_angles, neck_limits, turn_neck};
+    use crate::ragdoll::ragdoll_bones;
+    use crate::secondary::{SecondaryParams, SpringChain};
+    use crate::target::{
+        BezierTarget, Easing, LinearTarget, ParabolicTarget, SpringTarget, Target,
+    };
     use gdnative::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+    use std::iter::zip;
+    use std::path::Path;
+
+    fn straight_limb(texture_indices: [&[(usize, f32)]; 3]) -> Limb {
+        Limb {
+            displacement: Vector3::ZERO,
+            texture_displacement: 0,
+            name: "leg".to_string(),
+            body: texture_indices
+                .into_iter()
+                .map(|texture_indices| BodyPoint {
+                    dir: Vector2::new(1.0, 0.0),
+                    size: 1.0,
+                    texture_indices: texture_indices.to_vec(),
+                    discontinuous: false,
+                    limbs: Vec::new(),
+                })
+                .collect(),
+            role: None,
+            side: None,
+        }
+    }
+
+    fn leg_bones() -> HashMap<String, i64> {
+        (0..3).map(|i| (format!("leg_{}", i), i + 10)).collect()
+    }
 
     #[test]
     fn test_animal_blend() {
-        let animal_textures = [[Some((0, 1.0)), None], [Some((1, 1.0)), None]];
+        let animal_textures = [vec![(0, 1.0)], vec![(1, 1.0)]];
         let animals = [
             Animal::new(vec![
                 BodyPoint {
                     dir: Vector2::new(1.0, -1.0),
                     size: 1.0,
-                    texture_indices: animal_textures[0],
+                    texture_indices: animal_textures[0].clone(),
                     discontinuous: false,
                     limbs: Vec::new(),
                 },
                 BodyPoint {
                     dir: Vector2::new(1.0, -0.2),
                     size: 1.0,
-                    texture_indices: animal_textures[0],
+                    texture_indices: animal_textures[0].clone(),
                     discontinuous: false,
                     limbs: Vec::new(),
                 },
@@ -82,14 +146,14 @@ mod tests {
                 BodyPoint {
                     dir: Vector2::new(-1.0, 1.0),
                     size: 3.0,
-                    texture_indices: animal_textures[1],
+                    texture_indices: animal_textures[1].clone(),
                     discontinuous: false,
                     limbs: Vec::new(),
                 },
                 BodyPoint {
                     dir: Vector2::new(-1.0, 0.2),
                     size: 3.0,
-                    texture_indices: animal_textures[1],
+                    texture_indices: animal_textures[1].clone(),
                     discontinuous: false,
                     limbs: Vec::new(),
                 },
@@ -102,7 +166,7 @@ mod tests {
 
         let combined = animals.into_iter().zip(gradients).collect::<Vec<_>>();
 
-        let blended = blend_animals(&combined);
+        let blended = blend_animals(&combined).unwrap();
 
         assert_eq!(
             blended,
@@ -110,7 +174,7 @@ mod tests {
                 BodyPoint {
                     dir: Vector2 { x: -0.5, y: 0.5 },
                     size: 2.5,
-                    texture_indices: [Some((1, 0.75)), Some((0, 0.25))],
+                    texture_indices: vec![(1, 0.75), (0, 0.25)],
                     discontinuous: false,
                     limbs: Vec::new()
                 },
@@ -120,11 +184,1171 @@ mod tests {
                         y: -0.10000001
                     },
                     size: 1.5,
-                    texture_indices: [Some((0, 0.75)), Some((1, 0.25))],
+                    texture_indices: vec![(0, 0.75), (1, 0.25)],
                     discontinuous: false,
                     limbs: Vec::new()
                 }
             ])
         );
     }
+
+    #[test]
+    fn test_templates_load_from_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../rusty_game_jam_godot/animals");
+        let animals = load_animals(&dir).unwrap();
+
+        assert_eq!(animals["chicken"], animal_templates::chicken());
+        assert_eq!(animals["turtle"], animal_templates::turtle());
+        assert_eq!(animals["fox"], animal_templates::fox());
+    }
+
+    #[test]
+    fn test_animals_round_trip() {
+        let fox = animal_templates::fox();
+        for extension in ["ron", "json"] {
+            let path = std::env::temp_dir().join(format!("round_trip_fox.{}", extension));
+            save_animal(&fox, &path).unwrap();
+            assert_eq!(load_animal(&path).unwrap(), fox);
+        }
+        assert!(save_animal(&fox, Path::new("fox.txt")).is_err());
+    }
+
+    #[test]
+    fn test_limb_mesh_triangulation() {
+        let texture: &[(usize, f32)] = &[(0, 1.0)];
+        let limb = straight_limb([texture; 3]);
+        let mesh = build_limb_mesh(
+            &limb,
+            Vector3::ZERO,
+            &leg_bones(),
+            None,
+            (1.0, 1.0),
+            MeshStyle::default(),
+        )
+        .unwrap();
+
+        // 2 segments, each a fan of 4 triangles around the segment center
+        assert_eq!(mesh.vertices.len(), 2 * 4 * 3);
+        assert_eq!(mesh.indices, (0..24).collect::<Vec<_>>());
+        for segment in 0..2 {
+            let center = Vector3::new(segment as f32 + 0.5, 0.0, 0.0);
+            for tri in 0..4 {
+                assert_eq!(mesh.vertices[segment * 12 + tri * 3 + 2], center);
+            }
+        }
+        assert_eq!(mesh.vertices[0], Vector3::new(0.0, 0.5, 0.0));
+        assert_eq!(mesh.vertices[1], Vector3::new(0.0, -0.5, 0.0));
+        assert_eq!(mesh.vertices[4], Vector3::new(1.0, -0.5, 0.0));
+    }
+
+    #[test]
+    fn test_limb_mesh_texture_transition() {
+        let limb = straight_limb([&[(0, 1.0)], &[(2, 0.5), (0, 0.5)], &[(2, 1.0)]]);
+        let mesh = build_limb_mesh(
+            &limb,
+            Vector3::ZERO,
+            &leg_bones(),
+            None,
+            (0.5, 0.25),
+            MeshStyle::default(),
+        )
+        .unwrap();
+
+        // every texture of a segment shares the uv of the first atlas row, their rows are packed
+        // into the second uv and how much of each shows is in the color
+        assert_eq!(mesh.uvs[0], Vector2::new(0.0, 0.0));
+        assert_eq!(mesh.uv2s[0], Vector2::new(512.0, 0.0));
+        assert_eq!(mesh.colors[0], Color::from_rgba(1.0, 0.0, 0.0, 0.0));
+        assert_eq!(mesh.colors[4], Color::from_rgba(0.5, 0.5, 0.0, 0.0));
+        // the fade is linear along the segment, so the center of the fan is halfway between its
+        // ends rather than matching the end of the segment
+        assert_eq!(mesh.colors[2], Color::from_rgba(0.75, 0.25, 0.0, 0.0));
+
+        assert_eq!(mesh.uvs[12], Vector2::new(0.5, 0.0));
+        assert_eq!(mesh.uv2s[12], Vector2::new(2.0, 0.0));
+        assert_eq!(mesh.colors[12], Color::from_rgba(0.5, 0.5, 0.0, 0.0));
+        assert_eq!(mesh.uvs[16], Vector2::new(1.0, 0.25));
+        assert_eq!(mesh.colors[16], Color::from_rgba(1.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_three_way_texture_blend() {
+        let animals = [(0, 0.5), (1, 0.3), (2, 0.2)].map(|(texture, weight)| {
+            let limb = straight_limb([&[(texture, 1.0)]; 3]);
+            (Animal::new(limb.body), BodyGradient(vec![weight; 3]))
+        });
+        let blended = blend_animals(&animals).unwrap();
+        for point in &blended.body.body {
+            assert_eq!(point.texture_indices.len(), 3);
+            assert_eq!(point.texture_indices[0].0, 0);
+            assert!((point.texture_indices[2].1 - 0.2).abs() < 1e-5);
+        }
+        assert_eq!(blended.validate(), Vec::new());
+
+        let mut limb = blended.body;
+        limb.name = "leg".to_string();
+        let mesh = build_limb_mesh(
+            &limb,
+            Vector3::ZERO,
+            &leg_bones(),
+            None,
+            (1.0, 1.0),
+            MeshStyle::default(),
+        )
+        .unwrap();
+        assert_eq!(mesh.uv2s[0], Vector2::new(256.0, 2.0));
+        assert!((mesh.colors[0].g - 0.3).abs() < 1e-5);
+        assert_eq!(mesh.colors[0].a, 0.0);
+
+        // a fifth texture doesn't fit on a segment
+        let first = [(0, 0.4), (1, 0.3), (2, 0.2), (3, 0.1)];
+        assert_eq!(match_texture_indices(&first, &[(0, 0.5), (4, 0.5)]), None);
+    }
+
+    #[test]
+    fn test_limb_mesh_tube() {
+        let texture: &[(usize, f32)] = &[(0, 1.0)];
+        let limb = straight_limb([texture; 3]);
+        let mesh = build_limb_mesh(
+            &limb,
+            Vector3::ZERO,
+            &leg_bones(),
+            None,
+            (1.0, 1.0),
+            MeshStyle {
+                shape: LimbShape::Tube { sides: 6 },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // 2 segments, each a ring of 6 quads
+        assert_eq!(mesh.vertices.len(), 2 * 6 * 2 * 3);
+        assert_eq!(mesh.normals.len(), mesh.vertices.len());
+        for (vert, normal) in zip(&mesh.vertices, &mesh.normals) {
+            // the limb is as thick as it is wide, and the normals point straight out from it
+            let out = Vector3::new(0.0, vert.y, vert.z);
+            assert!((out.length() - 0.5).abs() < 1e-5);
+            assert!(normal.distance_to(out * 2.0) < 1e-5);
+        }
+        assert!(mesh.vertices.iter().any(|x| x.z > 0.4));
+        assert!(mesh.vertices.iter().any(|x| x.z < -0.4));
+
+        // the outside of every triangle is the clockwise side
+        for tri in mesh.vertices.chunks(3) {
+            let middle = (tri[0] + tri[1] + tri[2]) / 3.0;
+            let out = Vector3::new(0.0, middle.y, middle.z);
+            assert!((tri[1] - tri[0]).cross(tri[2] - tri[0]).dot(out) < 0.0);
+        }
+
+        let flat = build_limb_mesh(
+            &limb,
+            Vector3::ZERO,
+            &leg_bones(),
+            None,
+            (1.0, 1.0),
+            MeshStyle::default(),
+        )
+        .unwrap();
+        // ribbons face away from the front of the animal, where godot will flip them around
+        assert!(flat
+            .normals
+            .iter()
+            .all(|x| *x == Vector3::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_limb_mesh_tangents() {
+        let texture: &[(usize, f32)] = &[(0, 1.0)];
+        let limb = straight_limb([texture; 3]);
+        let build = |shape| {
+            let style = MeshStyle {
+                shape,
+                ..Default::default()
+            };
+            build_limb_mesh(&limb, Vector3::ZERO, &leg_bones(), None, (0.5, 0.25), style).unwrap()
+        };
+
+        // the texture runs along the limb, and up it is towards +y, the start of each block
+        let ribbon = build(LimbShape::Ribbon);
+        assert_eq!(ribbon.tangents.len(), ribbon.vertices.len());
+        for (tangent, normal) in zip(&ribbon.tangents, &ribbon.normals) {
+            assert!(tangent.normal.distance_to(Vector3::new(1.0, 0.0, 0.0)) < 1e-5);
+            assert_eq!(
+                normal.cross(tangent.normal) * tangent.d,
+                Vector3::new(0.0, 1.0, 0.0)
+            );
+        }
+
+        let tube = build(LimbShape::Tube { sides: 8 });
+        assert_eq!(tube.tangents.len(), tube.vertices.len());
+        for (tangent, normal) in zip(&tube.tangents, &tube.normals) {
+            assert!(tangent.normal.distance_to(Vector3::new(1.0, 0.0, 0.0)) < 1e-5);
+            assert!(tangent.normal.dot(*normal).abs() < 1e-5);
+        }
+
+        // vertices shared between triangles, listed out of order, still get their own tangent
+        let mut quad = LimbMeshData {
+            vertices: vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 1.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            uvs: vec![
+                Vector2::new(0.0, 1.0),
+                Vector2::new(1.0, 1.0),
+                Vector2::new(1.0, 0.0),
+                Vector2::new(0.0, 0.0),
+            ],
+            normals: vec![Vector3::new(0.0, 0.0, 1.0); 4],
+            indices: vec![2, 1, 0, 3, 2, 0],
+            ..Default::default()
+        };
+        quad.generate_tangents();
+        assert_eq!(
+            quad.tangents,
+            vec![
+                Plane {
+                    normal: Vector3::new(1.0, 0.0, 0.0),
+                    d: 1.0
+                };
+                4
+            ]
+        );
+    }
+
+    #[test]
+    fn test_limb_mesh_spline() {
+        let texture: &[(usize, f32)] = &[(0, 1.0)];
+        let mut limb = straight_limb([texture; 3]);
+        limb.body[1].dir = Vector2::new(0.0, 1.0);
+        limb.body[1].size = 0.5;
+        let build = |style| {
+            build_limb_mesh(&limb, Vector3::ZERO, &leg_bones(), None, (1.0, 1.0), style).unwrap()
+        };
+        let straight = build(MeshStyle::default());
+        let spline = build(MeshStyle {
+            outline: Outline::Spline { subdivisions: 4 },
+            ..Default::default()
+        });
+
+        // every segment is cut into 4 quads, each still a fan of 4 triangles
+        assert_eq!(spline.vertices.len(), 2 * 4 * 12);
+        // the ends of each segment stay where they were, so neighbouring segments still line up
+        for (a, b) in [(0, 0), (1, 1), (4, 40), (12, 48), (13, 49), (16, 88)] {
+            assert!(straight.vertices[a].distance_to(spline.vertices[b]) < 1e-5);
+        }
+        // and each piece starts where the last one ended, partway along the texture
+        for piece in 0..7 {
+            let (end, start) = (piece * 12 + 4, (piece + 1) * 12 + 1);
+            assert!(spline.vertices[end].distance_to(spline.vertices[start]) < 1e-5);
+        }
+        assert_eq!(spline.uvs[12], Vector2::new(0.25, 0.0));
+
+        // the second segment gets wider along a curve rather than a straight line, where it would
+        // be 0.75 wide halfway along
+        let width = spline.vertices[72].distance_to(spline.vertices[73]);
+        assert!(width > 0.5 && width < 0.74);
+    }
+
+    #[test]
+    fn test_limb_mesh_bone_weights() {
+        let texture: &[(usize, f32)] = &[(0, 1.0)];
+        let limb = straight_limb([texture; 3]);
+        let mesh = build_limb_mesh(
+            &limb,
+            Vector3::ZERO,
+            &leg_bones(),
+            None,
+            (1.0, 1.0),
+            MeshStyle::default(),
+        )
+        .unwrap();
+
+        let assert_weights = |weights: [f32; 4], expected: [f32; 4]| {
+            for (weight, expected) in zip(weights, expected) {
+                assert!((weight - expected).abs() < 1e-5, "{:?}", weights);
+            }
+        };
+
+        // start of the second segment, its end, and its center, each mostly owned by the bone
+        // closest to it and fading into the ones either side
+        assert_eq!(mesh.bones[12], [10, 11, 0, 0]);
+        assert_weights(mesh.weights[12], [5.0 / 6.0, 1.0 / 6.0, 0.0, 0.0]);
+        assert_eq!(mesh.bones[16], [11, 10, 12, 0]);
+        assert_weights(mesh.weights[16], [4.0 / 6.0, 1.0 / 6.0, 1.0 / 6.0, 0.0]);
+        assert_eq!(mesh.bones[14], [10, 11, 12, 0]);
+        assert_weights(mesh.weights[14], [0.5, 23.0 / 48.0, 1.0 / 48.0, 0.0]);
+        for weights in &mesh.weights {
+            assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        }
+
+        // the root of a limb blends into the bone it hangs off
+        let mut bones = leg_bones();
+        bones.insert("spine_1".to_string(), 1);
+        let mesh = build_limb_mesh(
+            &limb,
+            Vector3::ZERO,
+            &bones,
+            Some("spine_1"),
+            (1.0, 1.0),
+            MeshStyle::default(),
+        )
+        .unwrap();
+        assert_eq!(mesh.bones[0], [1, 10, 0, 0]);
+        assert_weights(mesh.weights[0], [5.0 / 6.0, 1.0 / 6.0, 0.0, 0.0]);
+        assert!(mesh.bones[12].contains(&1));
+        assert!(!mesh.bones[16].contains(&1));
+    }
+
+    #[test]
+    fn test_animal_validation() {
+        let mut animal = Animal::new(straight_limb([&[(0, 1.0)]; 3]).body);
+        assert_eq!(animal.validate(), Vec::new());
+
+        animal.body.body[1].size = f32::NAN;
+        animal.body.body[2].texture_indices = vec![(1, 1.0)];
+        animal.body.body[0].limbs = vec![Limb {
+            name: "spine".to_string(),
+            ..straight_limb([&[(0, 1.0)]; 3])
+        }];
+        animal.body.body[0].limbs[0].body.clear();
+
+        let errors = animal.validate();
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(
+            errors[0],
+            AnimalError::InvalidSize { index: 1, .. }
+        ));
+        assert_eq!(
+            errors[1],
+            AnimalError::IncompatibleTextures {
+                limb: "spine".to_string(),
+                index: 1
+            }
+        );
+        assert_eq!(
+            errors[2],
+            AnimalError::DuplicateLimbName("spine".to_string())
+        );
+        assert_eq!(errors[3], AnimalError::EmptyLimb("spine".to_string()));
+    }
+
+    #[test]
+    fn test_texture_limits() {
+        let mut animal = Animal::new(straight_limb([&[(0, 1.0)]; 3]).body);
+        animal.body.body[1].texture_indices = (0..5).map(|x| (x, 0.2)).collect();
+        animal.body.body[2].texture_indices = vec![(0, 0.5), (256, 0.5)];
+        assert_eq!(
+            animal.validate(),
+            vec![
+                AnimalError::TooManyTextures {
+                    limb: "spine".to_string(),
+                    index: 1,
+                    count: 5
+                },
+                AnimalError::TextureOutOfRange {
+                    limb: "spine".to_string(),
+                    index: 2,
+                    texture: 256
+                },
+            ]
+        );
+
+        // points saved as a pair of optional textures still load
+        let ron_point: BodyPoint = ron::from_str(
+            "(dir: (x: 1.0, y: 0.0), size: 1.0, texture_indices: (Some((2, 0.75)), Some((1, 0.25))))",
+        )
+        .unwrap();
+        assert_eq!(ron_point.texture_indices, vec![(2, 0.75), (1, 0.25)]);
+        let json_point: BodyPoint = serde_json::from_str(
+            r#"{"dir": {"x": 1.0, "y": 0.0}, "size": 1.0, "texture_indices": [[2, 1.0], null]}"#,
+        )
+        .unwrap();
+        assert_eq!(json_point.texture_indices, vec![(2, 1.0)]);
+    }
+
+    #[test]
+    fn test_blend_errors() {
+        assert_eq!(blend_animals(&[]), Err(AnimalError::NoAnimals));
+        assert_eq!(
+            blend_animals(&[(animal_templates::fox(), BodyGradient(Vec::new()))]),
+            Err(AnimalError::EmptyGradient)
+        );
+        assert_eq!(BodyGradient::decreasing_linear(1).0, vec![1.0]);
+
+        let blended =
+            blend_animals(&[(animal_templates::fox(), BodyGradient::decreasing_linear(8))]);
+        assert_eq!(blended, Err(AnimalError::NoWeight(7)));
+    }
+
+    #[test]
+    fn test_limb_blend() {
+        let with_leg = |name: &str, texture: usize, size: f32, displacement: Vector3| {
+            let texture_indices: &[(usize, f32)] = &[(texture, 1.0)];
+            let mut leg = straight_limb([texture_indices; 3]);
+            leg.name = name.to_string();
+            leg.displacement = displacement;
+            leg.body.iter_mut().for_each(|x| x.size = size);
+            let mut animal = Animal::new(straight_limb([texture_indices; 3]).body);
+            animal.body.body[1].limbs.push(leg);
+            animal
+        };
+        let animals = [
+            (
+                with_leg("leg_0", 0, 1.0, Vector3::new(0.0, 0.0, -1.0)),
+                BodyGradient(vec![0.25; 3]),
+            ),
+            (
+                with_leg("leg_1", 1, 3.0, Vector3::new(1.0, 0.0, -1.0)),
+                BodyGradient(vec![0.75; 3]),
+            ),
+        ];
+
+        let blended = blend_animals(&animals).unwrap();
+        let leg = &blended.body.body[1].limbs[0];
+
+        assert_eq!(leg.name, "leg_1");
+        assert_eq!(leg.displacement, Vector3::new(0.75, 0.0, -1.0));
+        assert!(leg.body.iter().all(|x| x.size == 2.5));
+        assert_eq!(leg.body[0].texture_indices, vec![(1, 0.75), (0, 0.25)]);
+    }
+
+    #[test]
+    fn test_limb_only_on_one_parent() {
+        let with_tail = |weight: f32| {
+            let texture: &[(usize, f32)] = &[(0, 1.0)];
+            let mut tail = straight_limb([texture; 3]);
+            tail.name = "tail".to_string();
+            let mut animal = Animal::new(straight_limb([texture; 3]).body);
+            animal.body.body[2].limbs.push(tail);
+            (animal, BodyGradient(vec![weight; 3]))
+        };
+        let without_tail = |weight: f32| {
+            let animal = Animal::new(straight_limb([&[(0, 1.0)]; 3]).body);
+            (animal, BodyGradient(vec![weight; 3]))
+        };
+
+        // the parent without a tail has most of the weight, so the tail is there but shrunk
+        let blended = blend_animals(&[without_tail(0.75), with_tail(0.25)]).unwrap();
+        let tail = &blended.body.body[2].limbs[0];
+        assert_eq!(tail.name, "tail");
+        assert!(tail.body.iter().all(|x| (x.size - 0.25).abs() < 1e-5));
+        assert!(tail.body.iter().all(|x| (x.dir.x - 0.25).abs() < 1e-5));
+
+        let blended = blend_animals(&[without_tail(0.9), with_tail(0.1)]).unwrap();
+        assert!(blended.body.body[2].limbs.is_empty());
+    }
+
+    #[test]
+    fn test_limb_gradients() {
+        let with_leg = |size: f32, gradient: Vec<f32>| {
+            let texture: &[(usize, f32)] = &[(0, 1.0)];
+            let mut leg = straight_limb([texture; 3]);
+            leg.body.iter_mut().for_each(|x| x.size = size);
+            let mut animal = Animal::new(straight_limb([texture; 3]).body);
+            animal.body.body[0].limbs.push(leg);
+            (animal, BodyGradient(gradient))
+        };
+        let blended = blend_animals(&[
+            with_leg(1.0, vec![0.75, 0.5, 0.25]),
+            with_leg(3.0, vec![0.25, 0.5, 0.75]),
+        ])
+        .unwrap();
+
+        // the leg starts out blended like the point it's attached to, and ends up blended like
+        // the parents are on average
+        let sizes = blended.body.body[0].limbs[0]
+            .body
+            .iter()
+            .map(|x| x.size)
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![1.5, 1.75, 2.0]);
+    }
+
+    #[test]
+    fn test_breeding_is_deterministic() {
+        let (turtle, fox) = (animal_templates::turtle(), animal_templates::fox());
+        let rates = MutationRates::new(4);
+
+        let (child, gradients) = breed(&turtle, &fox, &rates, 42).unwrap();
+        assert_eq!(child, breed(&turtle, &fox, &rates, 42).unwrap().0);
+        assert_ne!(child, breed(&turtle, &fox, &rates, 43).unwrap().0);
+        assert_eq!(child.validate(), Vec::new());
+        // the parents share every point of the child's spine between them
+        let len = turtle.body.body.len().max(fox.body.body.len());
+        assert_eq!(gradients[0].0.len(), len);
+        for (a, b) in zip(&gradients[0].0, &gradients[1].0) {
+            assert!((a + b - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_mutations_keep_animals_valid() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let rates = MutationRates {
+            limb_count: 1.0,
+            texture: 1.0,
+            ..MutationRates::new(4)
+        };
+        let (mut animal, _) = crossover(
+            &animal_templates::chicken(),
+            &animal_templates::fox(),
+            &mut rng,
+        )
+        .unwrap();
+        for _ in 0..100 {
+            mutate(&mut animal, &rates, &mut rng);
+            assert_eq!(animal.validate(), Vec::new());
+        }
+    }
+
+    #[test]
+    fn test_gradient_presets() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let linear = GradientPreset::Linear.gradients(&[5, 5, 5], &mut rng);
+        assert_eq!(linear[0].0, vec![1.0, 0.5, 0.0, 0.0, 0.0]);
+        assert_eq!(linear[1].0, vec![0.0, 0.5, 1.0, 0.5, 0.0]);
+        assert_eq!(linear[2].0, vec![0.0, 0.0, 0.0, 0.5, 1.0]);
+
+        let animals = [
+            animal_templates::chicken(),
+            animal_templates::turtle(),
+            animal_templates::fox(),
+        ];
+        for preset in [
+            GradientPreset::Even,
+            GradientPreset::Linear,
+            GradientPreset::Random,
+        ] {
+            let lens = animals
+                .iter()
+                .map(|x| x.body.body.len())
+                .collect::<Vec<_>>();
+            let gradients = preset.gradients(&lens, &mut rng);
+            let combined = animals.iter().cloned().zip(gradients).collect::<Vec<_>>();
+            assert!(blend_animals(&combined).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_limb_infos() {
+        let fox = animal_templates::fox();
+        let mut bones = HashMap::new();
+        let mut limbs = vec![&fox.body];
+        while let Some(limb) = limbs.pop() {
+            for (i, point) in limb.body.iter().enumerate() {
+                bones.insert(format!("{}_{}", limb.name, i), bones.len() as i64);
+                limbs.extend(&point.limbs);
+            }
+        }
+
+        let infos = limb_infos(&fox, &bones).unwrap();
+        let legs = infos.iter().filter(|x| x.role == LimbRole::Leg).count();
+        assert_eq!(legs, 4);
+        let spine = infos.iter().find(|x| x.name == "spine").unwrap();
+        assert_eq!(spine.role, LimbRole::Spine);
+        assert_eq!(spine.bones.len(), fox.body.body.len());
+        assert_eq!(spine.bones[0], bones["spine_0"]);
+        let front_leg = infos.iter().find(|x| x.name == "front_leg_2").unwrap();
+        assert_eq!(front_leg.side, LimbSide::Front);
+
+        let mut tailed = fox.clone();
+        let fluff = Limb {
+            name: "fluff".to_string(),
+            role: Some(LimbRole::Tail),
+            ..straight_limb([&[(0, 1.0)]; 3])
+        };
+        assert_eq!(fluff.role(), LimbRole::Tail);
+        assert_eq!(fluff.side(), LimbSide::Center);
+        assert_eq!(LimbRole::from_name("comb"), LimbRole::Appendage);
+        tailed.body.body.last_mut().unwrap().limbs.push(fluff);
+        assert_eq!(
+            limb_infos(&tailed, &bones),
+            Err(AnimalError::MissingBone("fluff_0".to_string()))
+        );
+    }
+
+    fn chicken_leg() -> Vec<Vector2> {
+        vec![
+            Vector2::ZERO,
+            Vector2::new(0.2, -0.7),
+            Vector2::new(0.0, -1.4),
+            Vector2::new(-0.3, -1.4),
+        ]
+    }
+
+    fn assert_lengths_kept(rest: &[Vector2], solved: &[Vector2]) {
+        assert_eq!(rest[0], solved[0]);
+        for (a, b) in rest.windows(2).zip(solved.windows(2)) {
+            assert!((a[0].distance_to(a[1]) - b[0].distance_to(b[1])).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_fabrik_keeps_knees_bending_the_right_way() {
+        let rest = chicken_leg();
+        let limits = JointLimit::from_rest(&rest, 2.5);
+        assert!(limits[0].max == 0.0 && limits[0].min < 0.0);
+        let solver = Fabrik {
+            iterations: 20,
+            tolerance: 0.05,
+            limits: limits.clone(),
+        };
+
+        let mut joints = rest.clone();
+        assert!(solver.solve(&mut joints, Vector2::new(-0.3, -1.0)));
+
+        // the second target is easiest to reach by bending the knee forwards, which isn't allowed
+        for target in [Vector2::new(-0.3, -1.0), Vector2::new(0.4, -1.1)] {
+            let mut joints = rest.clone();
+            solver.solve(&mut joints, target);
+            assert_lengths_kept(&rest, &joints);
+            for (limit, x) in limits.iter().zip(joints.windows(3)) {
+                let bend = bend_angle(x[1] - x[0], x[2] - x[1]);
+                assert!(bend >= limit.min - 1e-4 && bend <= limit.max + 1e-4);
+            }
+        }
+
+        // out of reach, the leg should stretch towards it without making it
+        let mut joints = rest.clone();
+        assert!(!solver.solve(&mut joints, Vector2::new(0.0, -5.0)));
+        assert_lengths_kept(&rest, &joints);
+    }
+
+    #[test]
+    fn test_ccd_reaches_target() {
+        let rest = (0..5)
+            .map(|i| Vector2::new(i as f32, 0.0))
+            .collect::<Vec<_>>();
+        let target = Vector2::new(3.0, 1.5);
+        let mut joints = rest.clone();
+        let solver = Ccd {
+            iterations: 50,
+            tolerance: 0.05,
+            limits: vec![JointLimit::symmetric(0.6); 3],
+        };
+        assert!(solver.solve(&mut joints, target));
+        assert_lengths_kept(&rest, &joints);
+        for x in joints.windows(3) {
+            assert!(bend_angle(x[1] - x[0], x[2] - x[1]).abs() <= 0.6 + 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_two_bone_solver() {
+        let rest = chicken_leg()[..3].to_vec();
+        let solver = TwoBone::from_rest(&rest, 0.01);
+        assert!(solver.bend < 0.0);
+        for target in [
+            Vector2::new(0.0, -1.0),
+            Vector2::new(0.5, -0.5),
+            Vector2::new(-1.0, -0.8),
+        ] {
+            let mut joints = rest.clone();
+            assert!(solver.solve(&mut joints, target));
+            assert_lengths_kept(&rest, &joints);
+            assert!(bend_angle(joints[1] - joints[0], joints[2] - joints[1]) < 0.0);
+        }
+
+        let mut joints = rest.clone();
+        assert!(!solver.solve(&mut joints, Vector2::new(3.0, 0.0)));
+        assert_lengths_kept(&rest, &joints);
+        assert!(
+            (joints[2] - Vector2::new(rest[0].distance_to(rest[1]) * 2.0, 0.0)).length() < 1e-3
+        );
+    }
+
+    #[test]
+    fn test_gait_phases() {
+        // a trot moves diagonal legs together
+        let trot = [
+            (LimbSide::Front, 0),
+            (LimbSide::Back, 1),
+            (LimbSide::Back, 0),
+            (LimbSide::Front, 1),
+        ]
+        .map(|(side, pair)| Gait::Trot.phase(side, pair, 2));
+        assert_eq!(trot[0], trot[1]);
+        assert_eq!(trot[2], trot[3]);
+        assert_eq!((trot[2] - trot[0]).abs(), 0.5);
+
+        // a walk moves one leg at a time
+        let mut walk = [
+            (LimbSide::Front, 0),
+            (LimbSide::Back, 1),
+            (LimbSide::Back, 0),
+            (LimbSide::Front, 1),
+        ]
+        .map(|(side, pair)| Gait::Walk.phase(side, pair, 2));
+        walk.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(walk, [0.0, 0.25, 0.5, 0.75]);
+
+        assert_eq!(Gait::for_speed(0.5, 1.0, 1), Gait::Walk);
+        assert_eq!(Gait::for_speed(5.0, 1.0, 1), Gait::Hop);
+        assert_eq!(Gait::for_speed(2.0, 1.0, 2), Gait::Trot);
+        assert_eq!(Gait::for_speed(-8.0, 1.0, 2), Gait::Gallop);
+    }
+
+    #[test]
+    fn test_gait_planner_steps() {
+        // a chicken walking, its legs should alternate
+        let mut planner = GaitPlanner::new(&[(LimbSide::Front, 1.0), (LimbSide::Back, 1.0)], 1.0);
+        assert!(planner.tick(1.0, 0.0).is_empty());
+
+        let delta = 0.01;
+        let velocity: f32 = -1.0;
+        let period = planner.stride() / velocity.abs();
+        let mut steps = Vec::new();
+        for _ in 0..(period * 2.0 / delta).round() as usize {
+            steps.extend(planner.tick(delta, velocity));
+        }
+        assert_eq!(planner.gait, Gait::Walk);
+        let legs = steps.iter().map(|x| x.leg).collect::<Vec<_>>();
+        assert!(legs.len() >= 3);
+        for pair in legs.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+        for step in steps {
+            assert!(step.reach < 0.0);
+            assert!(step.duration > 0.0 && step.duration < period);
+        }
+    }
+
+    /// flat ground at y = 0 with a step up to y = 0.5 from x = 1 onwards
+    struct StepGround;
+
+    impl Ground for StepGround {
+        fn raycast_down(&self, from: Vector2, depth: f32) -> Option<Vector2> {
+            let height = if from.x < 1.0 { 0.0 } else { 0.5 };
+            if from.y >= height && from.y - depth <= height {
+                Some(Vector2::new(from.x, height))
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_foot_placement() {
+        let root = Vector2::new(0.0, 1.0);
+        assert_eq!(
+            place_foot(&StepGround, root, 0.5, 1.0, 1.5),
+            Vector2::new(0.5, 0.0)
+        );
+        assert_eq!(
+            place_foot(&StepGround, root, 1.5, 1.0, 1.5),
+            Vector2::new(1.5, 0.5)
+        );
+        // too high up to find the ground
+        let root = Vector2::new(0.0, 10.0);
+        assert_eq!(
+            place_foot(&StepGround, root, -0.5, 1.0, 1.5),
+            Vector2::new(-0.5, 8.5)
+        );
+    }
+
+    #[test]
+    fn test_body_tilt() {
+        assert_eq!(body_tilt(&[]), 0.0);
+        assert_eq!(average_height(&[]), None);
+
+        let flat = [Vector2::new(-1.0, 0.0), Vector2::new(1.0, 0.0)];
+        assert_eq!(body_tilt(&flat), 0.0);
+        assert_eq!(average_height(&flat), Some(0.0));
+
+        let slope = [
+            Vector2::new(-1.0, -0.25),
+            Vector2::new(-0.9, -0.225),
+            Vector2::new(1.0, 0.25),
+            Vector2::new(1.1, 0.275),
+        ];
+        assert!((body_tilt(&slope) - 0.25f32.atan()).abs() < 1e-5);
+
+        // a cliff doesn't tip the body over
+        let cliff = [Vector2::new(0.0, 0.0), Vector2::new(0.1, 5.0)];
+        assert_eq!(body_tilt(&cliff), 0.5);
+    }
+
+    #[test]
+    fn test_behavior_states() {
+        let params = BehaviorParams::default();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut behavior = Behavior::new(params.clone());
+        let senses = Senses {
+            position: 0.0,
+            threats: vec![1.0, 20.0],
+            food: vec![-2.0],
+            herd: vec![-9.0],
+        };
+        assert_eq!(behavior.tick(0.1, &senses, &mut rng), -params.run_speed);
+        assert_eq!(behavior.state, BehaviorState::Flee);
+
+        // a hungry animal goes for food it can see, and eats it when it gets there
+        behavior.hunger = 0.9;
+        let senses = Senses {
+            threats: Vec::new(),
+            ..senses
+        };
+        assert_eq!(behavior.tick(0.1, &senses, &mut rng), -params.walk_speed);
+        assert_eq!(behavior.state, BehaviorState::SeekFood);
+        let senses = Senses {
+            position: -1.8,
+            ..senses
+        };
+        assert_eq!(behavior.tick(0.1, &senses, &mut rng), 0.0);
+        assert!(behavior.hunger < 0.01);
+
+        // once it's full it heads back to the herd
+        behavior.tick(0.1, &senses, &mut rng);
+        assert_eq!(behavior.state, BehaviorState::FollowHerd);
+    }
+
+    #[test]
+    fn test_flee_at_edge() {
+        let params = BehaviorParams::default();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // even a starving animal that's already after food runs from a threat at the very edge
+        let mut behavior = Behavior::new(params.clone());
+        behavior.hunger = 1.0;
+        behavior.state = BehaviorState::SeekFood;
+        let senses = Senses {
+            position: 0.0,
+            threats: vec![-params.flee_distance],
+            food: vec![2.0],
+            herd: Vec::new(),
+        };
+        assert_eq!(behavior.tick(0.1, &senses, &mut rng), params.run_speed);
+        assert_eq!(behavior.state, BehaviorState::Flee);
+
+        // but just past it the threat is ignored
+        behavior.state = BehaviorState::SeekFood;
+        let senses = Senses {
+            threats: vec![-params.flee_distance - 0.01],
+            ..senses
+        };
+        assert_eq!(behavior.tick(0.1, &senses, &mut rng), params.walk_speed);
+        assert_eq!(behavior.state, BehaviorState::SeekFood);
+    }
+
+    #[test]
+    fn test_behavior_is_deterministic() {
+        let senses = Senses::default();
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut behavior = Behavior::new(BehaviorParams::default());
+            (0..1000)
+                .map(|_| behavior.tick(0.05, &senses, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        let speeds = run(3);
+        assert_eq!(speeds, run(3));
+        assert!(speeds.contains(&0.0));
+        assert!(speeds.iter().any(|x| *x != 0.0));
+    }
+
+    #[test]
+    fn test_behavior_blends() {
+        let chicken = animal_templates::chicken();
+        let fox = animal_templates::fox();
+        let blended = BehaviorParams::blend(&[(&chicken.behavior, 1.0), (&fox.behavior, 3.0)]);
+        assert_eq!(
+            blended.walk_speed,
+            (chicken.behavior.walk_speed + fox.behavior.walk_speed * 3.0) / 4.0
+        );
+        assert_eq!(BehaviorParams::blend(&[]), BehaviorParams::default());
+    }
+
+    /// where `target` is every `step` seconds after it starts moving to `to`, up to `time`
+    fn sample(target: &mut dyn Target, to: Vector2, step: f32, time: f32) -> Vec<Vector2> {
+        target.update_target(to);
+        let mut samples = vec![target.target()];
+        for _ in 0..(time / step).round() as usize {
+            target.delta(step);
+            samples.push(target.target());
+        }
+        samples
+    }
+
+    fn assert_close(a: Vector2, b: Vector2) {
+        assert!(a.distance_to(b) < 1e-4, "{:?} isn't {:?}", a, b);
+    }
+
+    #[test]
+    fn test_easing() {
+        for easing in [Easing::Linear, Easing::EaseInOut, Easing::Smoothstep] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+            let samples = (0..=10)
+                .map(|x| easing.apply(x as f32 / 10.0))
+                .collect::<Vec<_>>();
+            assert!(samples.windows(2).all(|x| x[0] <= x[1]), "{:?}", easing);
+        }
+        assert!(Easing::EaseInOut.apply(0.25) < 0.25);
+        assert!(Easing::EaseInOut.apply(0.75) > 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::Smoothstep.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn test_linear_target() {
+        let to = Vector2::new(2.0, 4.0);
+        let samples = sample(&mut LinearTarget::new(Vector2::ZERO, 1.0), to, 0.25, 1.5);
+        assert_close(samples[0], Vector2::ZERO);
+        assert_close(samples[2], Vector2::new(1.0, 2.0));
+        assert_close(samples[6], to);
+
+        let mut eased = LinearTarget::new(Vector2::ZERO, 1.0).with_easing(Easing::EaseInOut);
+        let samples = sample(&mut eased, to, 0.25, 1.5);
+        assert_close(samples[1], Vector2::new(0.25, 0.5));
+        assert!(eased.done());
+    }
+
+    #[test]
+    fn test_parabolic_target() {
+        // straight up used to divide by zero
+        let up = Vector2::new(0.0, 2.0);
+        let samples = sample(
+            &mut ParabolicTarget::new(Vector2::ZERO, 1.0, 1.0),
+            up,
+            0.25,
+            1.5,
+        );
+        assert!(samples.iter().all(|x| x.x.is_finite() && x.y.is_finite()));
+        assert_close(samples[0], Vector2::ZERO);
+        assert_close(samples[6], up);
+
+        let across = Vector2::new(4.0, 0.0);
+        let samples = sample(
+            &mut ParabolicTarget::new(Vector2::ZERO, 1.0, 0.5),
+            across,
+            0.25,
+            1.0,
+        );
+        // halfway there, a quarter of the distance times the slope up
+        assert_close(samples[2], Vector2::new(2.0, 0.5));
+        assert!(samples.iter().all(|x| x.y >= 0.0));
+        assert_close(samples[4], across);
+    }
+
+    #[test]
+    fn test_bezier_target() {
+        let to = Vector2::new(3.0, 1.0);
+        let mut target = BezierTarget::new(Vector2::ZERO, 1.0, 2.0);
+        let samples = sample(&mut target, to, 0.1, 1.2);
+        assert_close(samples[0], Vector2::ZERO);
+        assert_close(samples[12], to);
+        // with the control points a third and two thirds of the way along, the middle of the curve
+        // is three quarters of the lift above the middle of the straight line
+        assert_close(samples[5], Vector2::new(1.5, 0.5 + 1.5));
+        assert!(samples[1..10].iter().all(|x| x.y > x.x / 3.0));
+        assert!(target.done());
+
+        // easing slows the start down without moving the ends
+        let mut eased = BezierTarget::new(Vector2::ZERO, 1.0, 2.0)
+            .with_controls(0.0, 1.0)
+            .with_easing(Easing::Smoothstep);
+        let eased_samples = sample(&mut eased, to, 0.1, 1.2);
+        assert_close(eased_samples[0], Vector2::ZERO);
+        assert_close(eased_samples[12], to);
+        assert!(eased_samples[1].distance_to(samples[0]) < samples[1].distance_to(samples[0]));
+    }
+
+    #[test]
+    fn test_spring_target() {
+        let to = Vector2::new(0.0, -3.0);
+        let mut target = SpringTarget::new(Vector2::ZERO, 0.5);
+        let samples = sample(&mut target, to, 1.0 / 60.0, 1.0);
+        // critically damped, so it closes in without ever overshooting
+        assert!(samples.windows(2).all(|x| x[1].y <= x[0].y));
+        assert!(samples.iter().all(|x| x.y >= to.y && x.x == 0.0));
+        assert!(samples[30].distance_to(to) < 0.05);
+        assert!(target.done());
+
+        // it follows a target that keeps moving without jumping to it
+        let samples = sample(&mut target, Vector2::ZERO, 1.0 / 60.0, 1.0 / 60.0);
+        assert!(samples[1].distance_to(samples[0]) < 0.1);
+        assert!(!target.done());
+    }
+
+    fn tail() -> Vec<Vector2> {
+        (0..4).map(|x| Vector2::new(x as f32 * 0.5, 0.0)).collect()
+    }
+
+    fn run_chain(chain: &mut SpringChain, rest: &[Vector2], acceleration: Vector2, time: f32) {
+        for _ in 0..(time * 60.0) as usize {
+            chain.step(1.0 / 60.0, rest, acceleration);
+        }
+    }
+
+    #[test]
+    fn test_chain_stays_at_rest() {
+        let rest = tail();
+        let params = SecondaryParams {
+            gravity: 0.0,
+            ..SecondaryParams::for_role(LimbRole::Tail).unwrap()
+        };
+        let mut chain = SpringChain::new(&rest, params);
+        run_chain(&mut chain, &rest, Vector2::ZERO, 1.0);
+        for (joint, rest) in zip(chain.joints(), &rest) {
+            assert!(joint.distance_to(*rest) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_secondary_roles() {
+        // legs and arms are moved by their targets, not left to swing
+        assert_eq!(SecondaryParams::for_role(LimbRole::Leg), None);
+        assert_eq!(SecondaryParams::for_role(LimbRole::Arm), None);
+        assert!(SecondaryParams::for_role(LimbRole::Tail).is_some());
+        assert!(SecondaryParams::for_role(LimbRole::Neck).is_some());
+    }
+
+    #[test]
+    fn test_chain_swings_when_accelerating() {
+        let rest = tail();
+        let mut chain = SpringChain::new(&rest, SecondaryParams::for_role(LimbRole::Tail).unwrap());
+        // speeding up upwards pushes the tip of the tail down
+        run_chain(&mut chain, &rest, Vector2::new(0.0, 20.0), 0.5);
+        let tip = *chain.joints().last().unwrap();
+        assert!(tip.y < -0.3, "{:?}", tip);
+        for (joints, rest) in zip(chain.joints().windows(2), rest.windows(2)) {
+            assert!((joints[0].distance_to(joints[1]) - rest[0].distance_to(rest[1])).abs() < 1e-4);
+        }
+        assert_eq!(chain.joints()[0], Vector2::ZERO);
+
+        // and once it stops it settles back down, sagging a little under gravity
+        run_chain(&mut chain, &rest, Vector2::ZERO, 3.0);
+        let settled = *chain.joints().last().unwrap();
+        assert!(settled.y < 0.0 && settled.y > tip.y, "{:?}", settled);
+
+        // the chain follows its rest pose when the body turns
+        let turned = rest.iter().map(|x| x.rotated(1.0)).collect::<Vec<_>>();
+        run_chain(&mut chain, &turned, Vector2::ZERO, 3.0);
+        assert!(chain.joints().last().unwrap().distance_to(turned[3]) < 0.3);
+    }
+
+    #[test]
+    fn test_look_angles() {
+        // the head is at the origin facing -x, like a spine
+        let neck = vec![
+            Vector2::ZERO,
+            Vector2::new(1.0, 0.0),
+            Vector2::new(2.0, 0.0),
+        ];
+        let limits = neck_limits(2);
+
+        let target = Vector2::new(-3.0, 1.0);
+        let angles = look_angles(&neck, target, &limits);
+        let posed = turn_neck(&neck, &angles);
+        assert!(bend_angle(posed[0] - posed[1], target - posed[1]).abs() < 0.01);
+        // looking up turns the head clockwise, and the head turns more than the rest of the neck
+        assert!(angles[0] < 0.0 && angles[1] < 0.0);
+        assert!(angles[0].abs() >= angles[1].abs());
+        assert_eq!(posed[2], neck[2]);
+        assert!((posed[0].distance_to(posed[1]) - 1.0).abs() < 1e-4);
+
+        // something behind the animal is only looked at as far as the neck bends
+        let angles = look_angles(&neck, Vector2::new(5.0, 0.5), &limits);
+        for (angle, limit) in zip(&angles, &limits) {
+            assert!(*angle >= limit.min && *angle <= limit.max);
+        }
+        assert_eq!(angles[0], limits[0].min);
+
+        assert_eq!(
+            look_angles(&neck, Vector2::new(-3.0, 0.0), &limits),
+            vec![0.0, 0.0]
+        );
+        assert!(look_angles(&neck[..1], target, &limits).is_empty());
+    }
+
+    #[test]
+    fn test_segment_capsules() {
+        let chicken = animal_templates::chicken();
+        let spine = segment_capsules(&chicken, false);
+        assert_eq!(spine.len(), chicken.body.body.len() - 1);
+        // the spine starts at the head and each capsule starts where the last one ended
+        assert_eq!(spine[0].from, Vector3::ZERO);
+        for (a, b) in spine.iter().zip(spine.iter().skip(1)) {
+            assert_eq!(a.to, b.from);
+        }
+        assert!(spine.iter().all(|x| x.radius > 0.0));
+        // the caps come on top of the height, so capsules don't reach past their segments
+        for capsule in &spine {
+            let length = capsule.from.distance_to(capsule.to);
+            assert!(
+                capsule.height() == 0.0 || capsule.height() + 2.0 * capsule.radius <= length + 1e-5
+            );
+        }
+        assert_eq!(capsule_height(3.0, 0.5), 2.0);
+        assert_eq!(capsule_height(0.5, 0.5), 0.0);
+
+        // legs stick out in front of and behind the body
+        let all = segment_capsules(&chicken, true);
+        assert!(all.len() > spine.len());
+        let legs = chicken.body.body.iter().flat_map(|x| &x.limbs);
+        for leg in legs.filter(|x| x.displacement.z != 0.0) {
+            assert!(all.iter().any(|x| x.from.z == leg.displacement.z));
+        }
+
+        assert_eq!(
+            ColliderShape::from_name("hull"),
+            Some(ColliderShape::ConvexHull)
+        );
+        assert_eq!(ColliderShape::from_name("sphere"), None);
+    }
+
+    #[test]
+    fn test_convex_hull() {
+        let points = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 1.0),
+            Vector2::new(2.0, 0.0),
+            Vector2::new(0.5, 0.2),
+            Vector2::new(2.0, 2.0),
+            Vector2::new(0.0, 2.0),
+            Vector2::new(2.0, 0.0),
+        ];
+        let hull = convex_hull(&points);
+        assert_eq!(
+            hull,
+            vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(2.0, 0.0),
+                Vector2::new(2.0, 2.0),
+                Vector2::new(0.0, 2.0),
+            ]
+        );
+
+        let extruded = extruded_hull(&points);
+        assert_eq!(extruded.len(), hull.len() * 2);
+        assert!(extruded.iter().all(|x| x.z.abs() == HALF_DEPTH));
+        assert_eq!(convex_hull(&points[..2]).len(), 2);
+    }
+
+    #[test]
+    fn test_ragdoll_bones() {
+        let chicken = animal_templates::chicken();
+        let bones = ragdoll_bones(&chicken);
+        let mut limbs = vec![&chicken.body];
+        let mut segments = 0;
+        while let Some(limb) = limbs.pop() {
+            segments += limb.body.len() - 1;
+            limbs.extend(limb.body.iter().flat_map(|x| &x.limbs));
+        }
+        assert_eq!(bones.len(), segments);
+
+        // only the head is free, and every joint can stay the way it was when the animal fell
+        assert_eq!(bones[0].bone_name, "spine_0");
+        assert_eq!(bones[0].limit, None);
+        for bone in &bones[1..] {
+            let limit = bone.limit.unwrap();
+            assert!(limit.min <= 0.0 && limit.max >= 0.0, "{:?}", bone);
+        }
+        assert!(bones.iter().all(|x| x.radius > 0.0));
+    }
 }
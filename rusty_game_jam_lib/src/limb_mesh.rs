@@ -0,0 +1,536 @@
+use crate::animal::{Animal, AnimalError, Limb};
+use gdnative::prelude::*;
+use itertools::Itertools;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::iter::zip;
+
+/// what a limb's mesh looks like from the side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimbShape {
+    /// a flat strip in the XY plane, only meant to be seen from the front
+    Ribbon,
+    /// a round tube with `sides` sides, as thick as the limb is wide
+    Tube { sides: usize },
+}
+
+/// how the outline of a limb gets from one body point to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outline {
+    /// a straight line, with a corner at every body point
+    Straight,
+    /// a catmull-rom spline through the body points and their sizes, cut into `subdivisions`
+    /// pieces per segment
+    Spline { subdivisions: usize },
+}
+
+/// how every limb of an animal gets meshed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshStyle {
+    pub shape: LimbShape,
+    pub outline: Outline,
+}
+
+impl Default for MeshStyle {
+    fn default() -> Self {
+        Self {
+            shape: LimbShape::Ribbon,
+            outline: Outline::Straight,
+        }
+    }
+}
+
+/// engine independent mesh data for a single limb, ready to be uploaded to a `SurfaceTool`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LimbMeshData {
+    pub vertices: Vec<Vector3>,
+    /// where the vertex is in the first row of the atlas, the other textures are further down
+    pub uvs: Vec<Vector2>,
+    /// the atlas rows of up to four textures, two packed into each component
+    pub uv2s: Vec<Vector2>,
+    /// how much of each of those textures shows
+    pub colors: Vec<Color>,
+    /// out of the clockwise side of each triangle, which godot treats as the front
+    pub normals: Vec<Vector3>,
+    /// along the texture, towards bigger u, with `d` saying which way the binormal points
+    pub tangents: Vec<Plane>,
+    pub bones: Vec<[i32; 4]>,
+    pub weights: Vec<[f32; 4]>,
+    pub indices: Vec<i32>,
+}
+
+impl LimbMeshData {
+    fn push_vertex(
+        &mut self,
+        vert: Vector3,
+        (uv, uv2, color): (Vector2, Vector2, Color),
+        bones: &[(i32, f32)],
+    ) {
+        let mut bone_ids = [0; 4];
+        let mut weights = [0.0; 4];
+        for (i, (bone, weight)) in bones.iter().take(4).enumerate() {
+            bone_ids[i] = *bone;
+            weights[i] = *weight;
+        }
+        self.indices.push(self.vertices.len() as i32);
+        self.vertices.push(vert);
+        self.uvs.push(uv);
+        self.uv2s.push(uv2);
+        self.colors.push(color);
+        self.bones.push(bone_ids);
+        self.weights.push(weights);
+    }
+
+    /// work out a tangent for every vertex from the triangles it's in and their uvs, so normal maps
+    /// line up with the texture. vertices shared between triangles get the average direction
+    pub(crate) fn generate_tangents(&mut self) {
+        let mut along = vec![(Vector3::ZERO, Vector3::ZERO); self.vertices.len()];
+        for tri in self.indices.chunks(3) {
+            let [a, b, c] = match tri {
+                [a, b, c] => [*a, *b, *c].map(|x| x as usize),
+                _ => break,
+            };
+            let (edge_b, edge_c) = (
+                self.vertices[b] - self.vertices[a],
+                self.vertices[c] - self.vertices[a],
+            );
+            let (uv_b, uv_c) = (self.uvs[b] - self.uvs[a], self.uvs[c] - self.uvs[a]);
+            let det = uv_b.x * uv_c.y - uv_c.x * uv_b.y;
+            let (along_u, along_v) = if det.abs() > f32::EPSILON {
+                (
+                    (edge_b * uv_c.y - edge_c * uv_b.y) / det,
+                    (edge_c * uv_b.x - edge_b * uv_c.x) / det,
+                )
+            } else {
+                (edge_b, edge_c)
+            };
+            for vertex in [a, b, c] {
+                along[vertex].0 += along_u;
+                along[vertex].1 += along_v;
+            }
+        }
+
+        self.tangents = zip(along, &self.normals)
+            .map(|((along_u, along_v), normal)| {
+                // keep the tangent flat against the surface
+                let tangent = along_u - *normal * normal.dot(along_u);
+                let tangent = if tangent.length_squared() > f32::EPSILON {
+                    tangent.normalized()
+                } else {
+                    Vector3::new(1.0, 0.0, 0.0)
+                };
+                // godot's binormals point up the texture, towards smaller v
+                let d = if normal.cross(tangent).dot(along_v) > 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                Plane { normal: tangent, d }
+            })
+            .collect();
+    }
+}
+
+type TextureWeights = Vec<(usize, f32)>;
+/// the most textures that can show on one segment of a limb, one for each channel of a color
+pub const MAX_TEXTURES: usize = 4;
+/// how many rows the texture atlas can have, two atlas rows are packed into each component of the
+/// second uv with the second one multiplied by this
+pub const MAX_TEXTURE_ROWS: usize = 256;
+const ROW_PACKING: f32 = MAX_TEXTURE_ROWS as f32;
+/// ribbons are wound anticlockwise seen from in front of the animal, so their front faces away
+/// from it. godot flips the normal of whichever side is being looked at
+const RIBBON_NORMAL: Vector3 = Vector3::new(0.0, 0.0, -1.0);
+/// position, normal and uv within its segment
+type TubeVertex = (Vector3, Vector3, (f32, f32));
+
+fn get_uv(texture_block_size: (f32, f32), x: usize, y: usize, uv: (f32, f32)) -> Vector2 {
+    Vector2::new(
+        (x as f32 + uv.0) * texture_block_size.0,
+        (y as f32 + uv.1) * texture_block_size.1,
+    )
+}
+
+/// line up the texture indices of two neighbouring body points so the same texture ends up in the
+/// same slot, padding with zero weight when a texture only exists on one side.
+/// returns `None` when the two points don't share a texture to transition through, or use more
+/// textures between them than fit on a segment
+pub fn match_texture_indices(
+    first: &[(usize, f32)],
+    second: &[(usize, f32)],
+) -> Option<(TextureWeights, TextureWeights)> {
+    if !first.iter().any(|a| second.iter().any(|b| a.0 == b.0)) {
+        return None;
+    }
+    let textures = first
+        .iter()
+        .chain(second)
+        .map(|x| x.0)
+        .unique()
+        .collect_vec();
+    if textures.len() > MAX_TEXTURES {
+        return None;
+    }
+    let weights = |point: &[(usize, f32)]| {
+        textures
+            .iter()
+            .map(|texture| {
+                let weight = point.iter().filter(|x| x.0 == *texture).map(|x| x.1).sum();
+                (*texture, weight)
+            })
+            .collect()
+    };
+    Some((weights(first), weights(second)))
+}
+
+/// how much each bone moves a vertex `t` of the way along a segment, as a uniform cubic b-spline
+/// over the four bones around it, so the weights fade smoothly from one bone to the next. the
+/// bone ending at the start of the segment owns most of it there, and the bone ending at the end
+/// owns most of it there. bones that show up more than once have their weights added up
+fn bone_weights(t: f32, bones: [i32; 4]) -> Vec<(i32, f32)> {
+    let u = 1.0 - t;
+    let (t2, t3) = (t * t, t * t * t);
+    let basis = [
+        u * u * u / 6.0,
+        (3.0 * t3 - 6.0 * t2 + 4.0) / 6.0,
+        (-3.0 * t3 + 3.0 * t2 + 3.0 * t + 1.0) / 6.0,
+        t3 / 6.0,
+    ];
+    let mut weights: Vec<(i32, f32)> = Vec::new();
+    for (bone, weight) in zip(bones, basis) {
+        match weights.iter_mut().find(|x| x.0 == bone) {
+            Some(existing) => existing.1 += weight,
+            None => weights.push((bone, weight)),
+        }
+    }
+    weights.retain(|x| x.1 > f32::EPSILON);
+    weights.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    weights
+}
+
+/// the uv of a vertex at `uv` within segment `i` of a limb, the packed atlas rows of its
+/// textures, and a color with how much each of them shows
+fn texture_vertex(
+    segment: usize,
+    texture_block_size: (f32, f32),
+    textures: (&TextureWeights, &TextureWeights),
+    uv: (f32, f32),
+) -> (Vector2, Vector2, Color) {
+    let mut rows = [0.0; MAX_TEXTURES];
+    let mut weights = [0.0; MAX_TEXTURES];
+    for (i, (start, end)) in zip(textures.0, textures.1).take(MAX_TEXTURES).enumerate() {
+        rows[i] = start.0 as f32;
+        // the blend fades linearly from how it is at the start of the segment to how it is at
+        // the end, so ribbon centers and spline subdivisions land in between
+        weights[i] = start.1 + (end.1 - start.1) * uv.0;
+    }
+    (
+        get_uv(texture_block_size, segment, 0, uv),
+        Vector2::new(
+            rows[0] + rows[1] * ROW_PACKING,
+            rows[2] + rows[3] * ROW_PACKING,
+        ),
+        Color::from_rgba(weights[0], weights[1], weights[2], weights[3]),
+    )
+}
+
+/// one segment of a limb's outline, between two neighbouring body points
+struct SegmentOutline {
+    start: Vector2,
+    end: Vector2,
+    /// which way the outline is heading at each end, a catmull-rom spline through the body points
+    tangents: (Vector2, Vector2),
+    /// half the width at each end
+    widths: (f32, f32),
+    /// how quickly the half width is changing at each end
+    width_slopes: (f32, f32),
+}
+
+impl SegmentOutline {
+    /// the position, and the vector from there to one edge of the outline, `t` of the way along
+    fn sample(&self, t: f32, outline: Outline) -> (Vector2, Vector2) {
+        let across = |tangent: Vector2, width: f32| tangent.tangent().normalized() * width;
+        if outline == Outline::Straight {
+            let (start, end) = (
+                across(self.tangents.0, self.widths.0),
+                across(self.tangents.1, self.widths.1),
+            );
+            return (
+                self.start.linear_interpolate(self.end, t),
+                start.linear_interpolate(end, t),
+            );
+        }
+
+        // cubic hermite basis functions and their derivatives
+        let (t2, t3) = (t * t, t * t * t);
+        let h = [
+            2.0 * t3 - 3.0 * t2 + 1.0,
+            t3 - 2.0 * t2 + t,
+            -2.0 * t3 + 3.0 * t2,
+            t3 - t2,
+        ];
+        let dh = [
+            6.0 * t2 - 6.0 * t,
+            3.0 * t2 - 4.0 * t + 1.0,
+            -6.0 * t2 + 6.0 * t,
+            3.0 * t2 - 2.0 * t,
+        ];
+        let hermite = |h: &[f32; 4], a: Vector2, da: Vector2, b: Vector2, db: Vector2| {
+            a * h[0] + da * h[1] + b * h[2] + db * h[3]
+        };
+        let (start_tangent, end_tangent) = self.tangents;
+        let posn = hermite(&h, self.start, start_tangent, self.end, end_tangent);
+        let heading = hermite(&dh, self.start, start_tangent, self.end, end_tangent);
+        let heading = if heading.length_squared() > f32::EPSILON {
+            heading
+        } else {
+            start_tangent.linear_interpolate(end_tangent, t)
+        };
+        let width = self.widths.0 * h[0]
+            + self.width_slopes.0 * h[1]
+            + self.widths.1 * h[2]
+            + self.width_slopes.1 * h[3];
+        (posn, across(heading, width.max(0.0)))
+    }
+}
+
+/// the outline of segment `i` of a limb, from body point `i` to the one after it
+fn segment_outline(limb: &Limb, i: usize, start: Vector2) -> SegmentOutline {
+    let points = &limb.body;
+    let (first, second) = (&points[i], &points[i + 1]);
+    let last_dir = if i == 0 { first.dir } else { points[i - 1].dir };
+    let average_dir = |a: Vector2, b: Vector2| (a + b) / 2.0;
+
+    let start_tangent = if first.discontinuous {
+        last_dir
+    } else {
+        average_dir(first.dir, last_dir)
+    };
+    // a discontinuous point keeps the end of the segment before it the same as its start
+    let (end_tangent, end_width) = if second.discontinuous {
+        (start_tangent, first.size * 0.5)
+    } else {
+        (average_dir(second.dir, first.dir), second.size * 0.5)
+    };
+    let widths = (first.size * 0.5, end_width);
+
+    let start_slope = match i.checked_sub(1).map(|x| &points[x]) {
+        Some(before) if !first.discontinuous => (widths.1 - before.size * 0.5) / 2.0,
+        _ => widths.1 - widths.0,
+    };
+    let end_slope = match points.get(i + 2) {
+        _ if second.discontinuous => 0.0,
+        Some(after) if !after.discontinuous => (after.size * 0.5 - widths.0) / 2.0,
+        Some(_) => (widths.1 - widths.0) / 2.0,
+        None => widths.1 - widths.0,
+    };
+
+    SegmentOutline {
+        start,
+        end: start + first.dir,
+        tangents: (start_tangent, end_tangent),
+        widths,
+        width_slopes: (start_slope, end_slope),
+    }
+}
+
+/// the triangles of a tube segment between a ring around `start` and a ring around `end`, each
+/// vertex with its normal and uv. `start_radius` and `end_radius` lie in the XY plane, across the
+/// limb, and the rings go round from them through +z
+fn tube_segment(
+    sides: usize,
+    (start, start_radius): (Vector3, Vector3),
+    (end, end_radius): (Vector3, Vector3),
+) -> Vec<[TubeVertex; 3]> {
+    let z = Vector3::new(0.0, 0.0, 1.0);
+    let axis = end - start;
+    // a tube that narrows along its length has normals that lean towards its narrow end
+    let slope = if axis.length_squared() > f32::EPSILON {
+        axis.normalized() * ((start_radius.length() - end_radius.length()) / axis.length())
+    } else {
+        Vector3::ZERO
+    };
+    // rings that shrink to a point still need to know which way is across the limb
+    let fallback = [start_radius, end_radius, Vector3::new(-axis.y, axis.x, 0.0)]
+        .into_iter()
+        .find(|x| x.length_squared() > f32::EPSILON)
+        .map_or(Vector3::new(0.0, 1.0, 0.0), |x| x.normalized());
+    let ring = |center: Vector3, radius: Vector3, side: usize| {
+        let angle = TAU * side as f32 / sides as f32;
+        let across = if radius.length_squared() > f32::EPSILON {
+            radius.normalized()
+        } else {
+            fallback
+        };
+        let outwards = across * angle.cos() + z * angle.sin();
+        let posn = center + outwards * radius.length();
+        (posn, (outwards + slope).normalized())
+    };
+
+    let mut triangles = Vec::new();
+    for side in 0..sides {
+        let v = |side: usize| side as f32 / sides as f32;
+        let (a, na) = ring(start, start_radius, side);
+        let (b, nb) = ring(start, start_radius, side + 1);
+        let (c, nc) = ring(end, end_radius, side + 1);
+        let (d, nd) = ring(end, end_radius, side);
+        let quad = [
+            (a, na, (0.0, v(side))),
+            (b, nb, (0.0, v(side + 1))),
+            (c, nc, (1.0, v(side + 1))),
+            (d, nd, (1.0, v(side))),
+        ];
+        let mut tris = [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]];
+        // godot draws the clockwise side of a triangle
+        let facing = (b - a).cross(c - a).dot(na + nc);
+        if facing > 0.0 {
+            for tri in &mut tris {
+                tri.swap(1, 2);
+            }
+        }
+        triangles.extend(tris);
+    }
+    triangles
+}
+
+/// build the mesh for a limb, `parent_bone` is the bone it hangs off, which its root blends into.
+/// ribbon segments are quads split into a fan of 4 triangles around their centers, tube segments
+/// are rings of quads. straight outlines have one quad or ring per segment, splines have one per
+/// subdivision
+pub fn build_limb_mesh(
+    limb: &Limb,
+    current_posn: Vector3,
+    bones: &HashMap<String, i64>,
+    parent_bone: Option<&str>,
+    texture_block_size: (f32, f32),
+    style: MeshStyle,
+) -> Result<LimbMeshData, AnimalError> {
+    if limb.body.is_empty() {
+        return Err(AnimalError::EmptyLimb(limb.name.clone()));
+    }
+    let mut data = LimbMeshData::default();
+    let mut current_posn = current_posn;
+    let subdivisions = match style.outline {
+        Outline::Straight => 1,
+        Outline::Spline { subdivisions } => subdivisions.max(1),
+    };
+
+    for ((i, first), (_, second)) in limb.body.iter().enumerate().tuple_windows() {
+        let outline = segment_outline(limb, i, Vector2::new(current_posn.x, current_posn.y));
+        let sample = |t: f32| {
+            let (posn, across) = outline.sample(t, style.outline);
+            (
+                Vector3::new(posn.x, posn.y, current_posn.z),
+                Vector3::new(across.x, across.y, 0.0),
+            )
+        };
+
+        let (first_texture_indices, second_texture_indices) =
+            match_texture_indices(&first.texture_indices, &second.texture_indices).ok_or_else(
+                || AnimalError::IncompatibleTextures {
+                    limb: limb.name.clone(),
+                    index: i,
+                },
+            )?;
+
+        let bone_id = |name: String| {
+            bones
+                .get(&name)
+                .map(|id| *id as i32)
+                .ok_or(AnimalError::MissingBone(name))
+        };
+        // before the first bone of the limb comes the bone it hangs off, if there is one
+        let limb_bone = |x: Option<usize>| match (x, parent_bone) {
+            (Some(x), _) => bone_id(format!("{}_{}", limb.name, x)),
+            (None, Some(parent)) => bone_id(parent.to_string()),
+            (None, None) => bone_id(format!("{}_0", limb.name)),
+        };
+        let segment_bones = [
+            limb_bone(i.checked_sub(2))?,
+            limb_bone(i.checked_sub(1))?,
+            limb_bone(Some(i))?,
+            limb_bone(Some(i + 1))?,
+        ];
+
+        let segment = i + limb.texture_displacement;
+        let textures = (&first_texture_indices, &second_texture_indices);
+        let mut push_vertex = |vert: Vector3, uv: (f32, f32)| {
+            let texture = texture_vertex(segment, texture_block_size, textures, uv);
+            data.push_vertex(vert, texture, &bone_weights(uv.0, segment_bones));
+        };
+        let mut normals = Vec::new();
+        for piece in 0..subdivisions {
+            let (from, to) = (
+                piece as f32 / subdivisions as f32,
+                (piece + 1) as f32 / subdivisions as f32,
+            );
+            let ((start, start_across), (end, end_across)) = (sample(from), sample(to));
+            match style.shape {
+                LimbShape::Ribbon => {
+                    let corners = [
+                        (start - start_across, (from, 0.0)),
+                        (start + start_across, (from, 1.0)),
+                        (end + end_across, (to, 1.0)),
+                        (end - end_across, (to, 0.0)),
+                    ];
+                    let middle = (from + to) / 2.0;
+                    let center = (sample(middle).0, (middle, 0.5));
+                    let quad = [
+                        [corners[0], corners[1], center],
+                        [corners[1], corners[2], center],
+                        [corners[2], corners[3], center],
+                        [corners[3], corners[0], center],
+                    ];
+
+                    for (vert, uv) in quad.into_iter().flatten() {
+                        push_vertex(vert, uv);
+                        normals.push(RIBBON_NORMAL);
+                    }
+                }
+                LimbShape::Tube { sides } => {
+                    let triangles = tube_segment(sides, (start, start_across), (end, end_across));
+                    for (vert, normal, uv) in triangles.into_iter().flatten() {
+                        push_vertex(vert, (from + (to - from) * uv.0, uv.1));
+                        normals.push(normal);
+                    }
+                }
+            }
+        }
+        data.normals.extend(normals);
+
+        current_posn += Vector3::new(first.dir.x, first.dir.y, 0.0);
+    }
+    data.generate_tangents();
+    Ok(data)
+}
+
+/// build the meshes for every limb of an animal, `bones` maps bone names to skeleton bone ids
+pub fn build_animal_meshes<'a>(
+    animal: &'a Animal,
+    bones: &HashMap<String, i64>,
+    texture_block_size: (f32, f32),
+    style: MeshStyle,
+) -> Result<Vec<(&'a Limb, LimbMeshData)>, AnimalError> {
+    let mut meshes = Vec::new();
+    let mut limbs = vec![(&animal.body, Vector3::ZERO, None)];
+    while let Some((limb, current_posn, parent_bone)) = limbs.pop() {
+        let mesh = build_limb_mesh(
+            limb,
+            current_posn + Vector3::new(limb.displacement.x, limb.displacement.y, 0.0),
+            bones,
+            parent_bone.as_deref(),
+            texture_block_size,
+            style,
+        )?;
+        meshes.push((limb, mesh));
+        let mut current_posn = current_posn;
+        for (i, point) in limb.body.iter().enumerate() {
+            for new_limb in &point.limbs {
+                limbs.push((new_limb, current_posn, Some(format!("{}_{}", limb.name, i))));
+            }
+            current_posn += Vector3::new(point.dir.x, point.dir.y, 0.0);
+        }
+    }
+    Ok(meshes)
+}
@@ -0,0 +1,54 @@
+use crate::ik::{bend_angle, JointLimit};
+use gdnative::prelude::*;
+
+/// how far the joint closest to the head can turn either way, joints further down the neck turn
+/// less
+const MAX_LOOK: f32 = 0.8;
+/// the head is turned, then the turn is worked out again from where the head ended up
+const ITERATIONS: usize = 3;
+
+/// limits for each joint of a neck `joints` long, from the head down
+pub fn neck_limits(joints: usize) -> Vec<JointLimit> {
+    (0..joints)
+        .map(|i| JointLimit::symmetric(MAX_LOOK / (i + 1) as f32))
+        .collect()
+}
+
+/// where the joints of `neck` end up when each joint after the head turns by `angles`. the last
+/// joint stays where it is and everything towards the head turns around it
+pub fn turn_neck(neck: &[Vector2], angles: &[f32]) -> Vec<Vector2> {
+    let mut posed = neck.to_vec();
+    let mut turned = 0.0;
+    for i in (0..neck.len().saturating_sub(1)).rev() {
+        turned += angles.get(i).copied().unwrap_or(0.0);
+        posed[i] = posed[i + 1] + (neck[i] - neck[i + 1]).rotated(turned);
+    }
+    posed
+}
+
+/// how much each joint of a neck has to turn for the head to face `target`, anticlockwise. `neck`
+/// goes from the head down to the last joint that turns, and the head faces away from the joint
+/// after it. the turn is spread out down the neck, with any joint that hits its limit leaving the
+/// rest to the joints after it
+pub fn look_angles(neck: &[Vector2], target: Vector2, limits: &[JointLimit]) -> Vec<f32> {
+    let joints = neck.len().saturating_sub(1);
+    let mut angles = vec![0.0; joints];
+    if joints == 0 {
+        return angles;
+    }
+    for _ in 0..ITERATIONS {
+        let posed = turn_neck(neck, &angles);
+        let (facing, to_target) = (posed[0] - posed[1], target - posed[1]);
+        if facing.length_squared() <= f32::EPSILON || to_target.length_squared() <= f32::EPSILON {
+            break;
+        }
+        let mut remaining = bend_angle(facing, to_target);
+        for (i, angle) in angles.iter_mut().enumerate() {
+            let limit = limits.get(i).unwrap_or(&JointLimit::FREE);
+            let turned = limit.clamp(*angle + remaining / (joints - i) as f32);
+            remaining -= turned - *angle;
+            *angle = turned;
+        }
+    }
+    angles
+}
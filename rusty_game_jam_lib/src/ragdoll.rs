@@ -0,0 +1,63 @@
+use crate::animal::Animal;
+use crate::collider::segment_radius;
+use crate::ik::{bend_angle, limits_for, JointLimit};
+use gdnative::prelude::*;
+use itertools::Itertools;
+
+/// how far a limb can swing either way where it joins the limb it hangs off
+const ROOT_SWING: f32 = 1.0;
+
+/// one bone of an animal that turns into a physics body when it goes limp
+#[derive(Debug, Clone, PartialEq)]
+pub struct RagdollBone {
+    pub bone_name: String,
+    /// from the start of the bone to the start of the next one
+    pub segment: Vector2,
+    /// how far in front of or behind the skeleton the bone's mesh is
+    pub depth: f32,
+    pub radius: f32,
+    /// how far the bone can turn away from where it was when the animal went limp, relative to
+    /// the bone it hangs off. the very first bone doesn't hang off anything
+    pub limit: Option<JointLimit>,
+}
+
+/// the bones of an animal that get a physics body, which is every bone but the tip of each limb.
+/// joints bend as far as the limb's IK allows, measured from the limb's rest pose
+pub fn ragdoll_bones(animal: &Animal) -> Vec<RagdollBone> {
+    let mut bones = Vec::new();
+    let mut limbs = vec![(&animal.body, true)];
+    while let Some((limb, root)) = limbs.pop() {
+        let mut joints = vec![Vector2::ZERO];
+        for point in limb.body.iter().take(limb.body.len().saturating_sub(1)) {
+            joints.push(*joints.last().unwrap() + point.dir);
+        }
+        let limits = limits_for(limb.role(), &joints);
+        let segments = limb.body.iter().map(|x| x.dir).collect::<Vec<_>>();
+
+        for (i, (first, second)) in limb.body.iter().tuple_windows().enumerate() {
+            let limit = if i == 0 {
+                (!root).then(|| JointLimit::symmetric(ROOT_SWING))
+            } else {
+                // the joints start out bent the way they are at rest, so the limits are shifted
+                // by that much, and never stop a joint staying how it was built
+                let rest = bend_angle(segments[i - 1], segments[i]);
+                limits.get(i - 1).map(|x| JointLimit {
+                    min: (x.min - rest).min(0.0),
+                    max: (x.max - rest).max(0.0),
+                })
+            };
+            bones.push(RagdollBone {
+                bone_name: format!("{}_{}", limb.name, i),
+                segment: first.dir,
+                depth: limb.displacement.z,
+                radius: segment_radius(first, second),
+                limit,
+            });
+        }
+
+        for point in &limb.body {
+            limbs.extend(point.limbs.iter().map(|x| (x, false)));
+        }
+    }
+    bones
+}
@@ -0,0 +1,94 @@
+use crate::animal::LimbRole;
+use gdnative::prelude::*;
+
+/// how a dangly chain of bones moves when it isn't being driven by anything
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SecondaryParams {
+    /// how strongly the chain is pulled back to its rest pose
+    pub stiffness: f32,
+    /// how much of its speed the chain loses every second, from 0 to 1
+    pub damping: f32,
+    /// how hard the chain is pulled down
+    pub gravity: f32,
+}
+
+impl SecondaryParams {
+    /// the chains of each role that get simulated, the rest are animated some other way
+    pub fn for_role(role: LimbRole) -> Option<Self> {
+        match role {
+            // the end of the spine is the tail
+            LimbRole::Spine | LimbRole::Tail => Some(Self {
+                stiffness: 60.0,
+                damping: 0.9,
+                gravity: 4.0,
+            }),
+            LimbRole::Neck => Some(Self {
+                stiffness: 150.0,
+                damping: 0.95,
+                gravity: 2.0,
+            }),
+            LimbRole::Appendage => Some(Self {
+                stiffness: 30.0,
+                damping: 0.8,
+                gravity: 6.0,
+            }),
+            LimbRole::Leg | LimbRole::Arm => None,
+        }
+    }
+}
+
+/// a verlet rope that springs back to its rest pose, and lags behind and swings when the body it's
+/// attached to speeds up or slows down. joints are relative to the root of the chain, which
+/// doesn't move
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpringChain {
+    pub params: SecondaryParams,
+    joints: Vec<Vector2>,
+    previous: Vec<Vector2>,
+    lengths: Vec<f32>,
+}
+
+impl SpringChain {
+    /// `rest` is the chain's joints at rest, relative to its root
+    pub fn new(rest: &[Vector2], params: SecondaryParams) -> Self {
+        Self {
+            params,
+            joints: rest.to_vec(),
+            previous: rest.to_vec(),
+            lengths: rest.windows(2).map(|x| x[0].distance_to(x[1])).collect(),
+        }
+    }
+
+    pub fn joints(&self) -> &[Vector2] {
+        &self.joints
+    }
+
+    /// move the chain along by `delta` seconds. `rest` is where the joints would be if the chain
+    /// wasn't moving, which turns with the body, and `acceleration` is the body's
+    pub fn step(&mut self, delta: f32, rest: &[Vector2], acceleration: Vector2) {
+        if delta <= 0.0 || self.joints.len() < 2 || rest.len() != self.joints.len() {
+            return;
+        }
+        let params = self.params;
+        // the chain is carried along with the body, so speeding up pushes it backwards
+        let force = Vector2::new(0.0, -params.gravity) - acceleration;
+        let keep = (1.0 - params.damping).powf(delta);
+        let joints = self.joints.iter_mut().zip(&mut self.previous).zip(rest);
+        for ((joint, previous), rest) in joints.skip(1) {
+            let velocity = (*joint - *previous) * keep;
+            let spring = (*rest - *joint) * params.stiffness;
+            *previous = *joint;
+            *joint += velocity + (force + spring) * (delta * delta);
+        }
+
+        // pull each joint back to the right distance from the one before it, working out from the
+        // root so the root stays put
+        self.joints[0] = Vector2::ZERO;
+        for i in 1..self.joints.len() {
+            let offset = self.joints[i] - self.joints[i - 1];
+            if offset.length_squared() > f32::EPSILON {
+                self.joints[i] = self.joints[i - 1] + offset.normalized() * self.lengths[i - 1];
+            }
+        }
+    }
+}
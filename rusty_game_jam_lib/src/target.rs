@@ -0,0 +1,297 @@
+use gdnative::prelude::*;
+
+/// a point that moves smoothly towards wherever it was last told to go
+pub trait Target {
+    fn update_target(&mut self, target: Vector2);
+    fn delta(&mut self, delta: f32);
+    fn target(&self) -> Vector2;
+    fn elapsed(&self) -> f32;
+    /// how long moving to a new target takes
+    fn set_time(&mut self, time: f32);
+    /// whether the last move has finished
+    fn done(&self) -> bool;
+}
+
+/// how progress through a move speeds up and slows down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseInOut,
+    Smoothstep,
+}
+
+impl Easing {
+    /// maps progress from 0 to 1 onto eased progress from 0 to 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+                }
+            }
+            Self::Smoothstep => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// moves in a straight line, nothing steps or swings like this any more but it's kept to check
+/// easing against
+#[cfg(test)]
+pub struct LinearTarget {
+    prev: Vector2,
+    curr: Vector2,
+    elapsed: f32,
+    time: f32,
+    easing: Easing,
+}
+
+#[cfg(test)]
+impl LinearTarget {
+    pub fn new(target: Vector2, time: f32) -> Self {
+        Self {
+            prev: target,
+            curr: target,
+            elapsed: f32::MAX,
+            time,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn with_easing(self, easing: Easing) -> Self {
+        Self { easing, ..self }
+    }
+}
+
+#[cfg(test)]
+impl Target for LinearTarget {
+    fn update_target(&mut self, target: Vector2) {
+        self.prev = self.curr;
+        self.curr = target;
+        self.elapsed = 0.0;
+    }
+
+    fn delta(&mut self, delta: f32) {
+        self.elapsed += delta;
+    }
+
+    fn target(&self) -> Vector2 {
+        if self.elapsed > self.time {
+            self.curr
+        } else {
+            self.prev
+                .linear_interpolate(self.curr, self.easing.apply(self.elapsed / self.time))
+        }
+    }
+
+    fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    fn done(&self) -> bool {
+        self.elapsed > self.time
+    }
+}
+
+/// moves in a parabolic arc, higher the further it has to go
+pub struct ParabolicTarget {
+    prev: Vector2,
+    curr: Vector2,
+    elapsed: f32,
+    arc_time: f32,
+    arc_slope: f32,
+}
+
+impl ParabolicTarget {
+    pub fn new(target: Vector2, arc_time: f32, arc_slope: f32) -> Self {
+        Self {
+            prev: target,
+            curr: target,
+            elapsed: f32::MAX,
+            arc_time,
+            arc_slope,
+        }
+    }
+}
+
+impl Target for ParabolicTarget {
+    fn update_target(&mut self, target: Vector2) {
+        self.prev = self.curr;
+        self.curr = target;
+        self.elapsed = 0.0;
+    }
+
+    fn delta(&mut self, delta: f32) {
+        self.elapsed += delta;
+    }
+
+    fn target(&self) -> Vector2 {
+        if self.elapsed > self.arc_time {
+            self.curr
+        } else {
+            // the peak is a quarter of the distance times the slope, halfway there
+            let t = self.elapsed / self.arc_time;
+            let height = self.arc_slope * self.prev.distance_to(self.curr) * t * (1.0 - t);
+            self.prev.linear_interpolate(self.curr, t) + Vector2::new(0.0, height)
+        }
+    }
+
+    fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    fn set_time(&mut self, time: f32) {
+        self.arc_time = time;
+    }
+
+    fn done(&self) -> bool {
+        self.elapsed > self.arc_time
+    }
+}
+
+/// follows a cubic bezier curve, lifted up off the straight line between the two points
+pub struct BezierTarget {
+    prev: Vector2,
+    curr: Vector2,
+    elapsed: f32,
+    time: f32,
+    /// how far above the straight line the control points are
+    lift: f32,
+    /// how far along the straight line the two control points are, from 0 to 1
+    controls: (f32, f32),
+    easing: Easing,
+}
+
+impl BezierTarget {
+    pub fn new(target: Vector2, time: f32, lift: f32) -> Self {
+        Self {
+            prev: target,
+            curr: target,
+            elapsed: f32::MAX,
+            time,
+            lift,
+            controls: (1.0 / 3.0, 2.0 / 3.0),
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn with_controls(self, first: f32, second: f32) -> Self {
+        Self {
+            controls: (first, second),
+            ..self
+        }
+    }
+
+    pub fn with_easing(self, easing: Easing) -> Self {
+        Self { easing, ..self }
+    }
+}
+
+impl Target for BezierTarget {
+    fn update_target(&mut self, target: Vector2) {
+        self.prev = self.curr;
+        self.curr = target;
+        self.elapsed = 0.0;
+    }
+
+    fn delta(&mut self, delta: f32) {
+        self.elapsed += delta;
+    }
+
+    fn target(&self) -> Vector2 {
+        if self.elapsed > self.time {
+            return self.curr;
+        }
+        let lift = Vector2::new(0.0, self.lift);
+        let points = [
+            self.prev,
+            self.prev.linear_interpolate(self.curr, self.controls.0) + lift,
+            self.prev.linear_interpolate(self.curr, self.controls.1) + lift,
+            self.curr,
+        ];
+        let t = self.easing.apply(self.elapsed / self.time);
+        let u = 1.0 - t;
+        points[0] * (u * u * u)
+            + points[1] * (3.0 * u * u * t)
+            + points[2] * (3.0 * u * t * t)
+            + points[3] * (t * t * t)
+    }
+
+    fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    fn done(&self) -> bool {
+        self.elapsed > self.time
+    }
+}
+
+/// a critically damped spring, follows moving targets smoothly without overshooting
+pub struct SpringTarget {
+    position: Vector2,
+    velocity: Vector2,
+    goal: Vector2,
+    elapsed: f32,
+    /// how stiff the spring is, higher catches up faster
+    frequency: f32,
+}
+
+impl SpringTarget {
+    /// `time` is roughly how long the spring takes to settle
+    pub fn new(target: Vector2, time: f32) -> Self {
+        let mut spring = Self {
+            position: target,
+            velocity: Vector2::ZERO,
+            goal: target,
+            elapsed: f32::MAX,
+            frequency: 0.0,
+        };
+        spring.set_time(time);
+        spring
+    }
+}
+
+impl Target for SpringTarget {
+    fn update_target(&mut self, target: Vector2) {
+        self.goal = target;
+        self.elapsed = 0.0;
+    }
+
+    fn delta(&mut self, delta: f32) {
+        self.elapsed += delta;
+        let omega = self.frequency;
+        let offset = self.position - self.goal;
+        let decay = (-omega * delta).exp();
+        let change = (self.velocity + offset * omega) * delta;
+        self.position = self.goal + (offset + change) * decay;
+        self.velocity = (self.velocity - change * omega) * decay;
+    }
+
+    fn target(&self) -> Vector2 {
+        self.position
+    }
+
+    fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    fn set_time(&mut self, time: f32) {
+        // a critically damped spring is within 1% of where it's going after about 6.6 / frequency
+        self.frequency = 6.6 / time.max(f32::EPSILON);
+    }
+
+    fn done(&self) -> bool {
+        self.position.distance_to(self.goal) < 0.01 && self.velocity.length() < 0.01
+    }
+}